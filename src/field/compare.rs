@@ -0,0 +1,345 @@
+use std::path::Path;
+
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+// Wang et al. (2004): 11x11 Gaussian window with sigma 1.5
+const SSIM_SIGMA: f64 = 1.5;
+const SSIM_RADIUS: isize = 5;
+const SSIM_K1: f64 = 0.01;
+const SSIM_K2: f64 = 0.03;
+
+/// Differences between a field and a reference, over the compared cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffMetrics {
+    /// number of cells compared
+    pub count: usize,
+    pub rmse: f32,
+    pub mae: f32,
+    pub max_abs_error: f32,
+    /// signed mean of field minus reference
+    pub bias: f32,
+    /// dB, with the reference's value range as the peak; infinite for identical fields and
+    /// negative infinity when the reference is flat but the field differs from it
+    pub psnr: f32,
+    /// mean structural similarity, 1 for identical fields
+    pub ssim: f32,
+}
+
+/// Separable Gaussian blur with clamped edges.
+fn blur(data: &[f64], width: usize, kernel: &[f64]) -> Vec<f64> {
+    let height = data.len() / width;
+    let radius = (kernel.len() / 2) as isize;
+    let pass = |source: &[f64], dx: isize, dy: isize| -> Vec<f64> {
+        let mut result = vec![0.0; source.len()];
+        result
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    *out = kernel
+                        .iter()
+                        .enumerate()
+                        .map(|(k, w)| {
+                            let offset = k as isize - radius;
+                            let sx = (x as isize + offset * dx).clamp(0, width as isize - 1);
+                            let sy = (y as isize + offset * dy).clamp(0, height as isize - 1);
+                            w * source[sy as usize * width + sx as usize]
+                        })
+                        .sum();
+                }
+            });
+        result
+    };
+    pass(&pass(data, 1, 0), 0, 1)
+}
+
+impl Field {
+    /// Whether cell `i` takes part in a comparison restricted to the non-zero cells of `mask`.
+    fn compared(mask: Option<&Field>, i: usize) -> bool {
+        mask.is_none_or(|m| m.flattened_field[i] != 0.0)
+    }
+
+    fn check_comparable(
+        &self,
+        reference: &Field,
+        mask: Option<&Field>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_same_size(reference)?;
+        if let Some(mask) = mask {
+            self.check_same_size(mask)?;
+        }
+        let finite = (0..self.flattened_field.len())
+            .into_par_iter()
+            .filter(|&i| Self::compared(mask, i))
+            .all(|i| {
+                self.flattened_field[i].is_finite() && reference.flattened_field[i].is_finite()
+            });
+        if !finite {
+            return Err("Compared cells must hold finite values".into());
+        }
+        Ok(())
+    }
+
+    /// Error metrics of this field against `reference`, over the non-zero cells of `mask` if
+    /// given. SSIM windows still read the cells around the mask.
+    pub fn compare(
+        &self,
+        reference: &Field,
+        mask: Option<&Field>,
+    ) -> Result<DiffMetrics, Box<dyn std::error::Error>> {
+        self.check_comparable(reference, mask)?;
+
+        let (count, sum, sum_abs, sum_sqr, max, min_ref, max_ref) = (0..self.flattened_field.len())
+            .into_par_iter()
+            .filter(|&i| Self::compared(mask, i))
+            .map(|i| {
+                let r = reference.flattened_field[i] as f64;
+                let d = self.flattened_field[i] as f64 - r;
+                (1_usize, d, d.abs(), d * d, d.abs(), r, r)
+            })
+            .reduce(
+                || (0, 0.0, 0.0, 0.0, 0.0, f64::MAX, f64::MIN),
+                |a, b| {
+                    (
+                        a.0 + b.0,
+                        a.1 + b.1,
+                        a.2 + b.2,
+                        a.3 + b.3,
+                        a.4.max(b.4),
+                        a.5.min(b.5),
+                        a.6.max(b.6),
+                    )
+                },
+            );
+        if count == 0 {
+            return Err("No cells to compare".into());
+        }
+
+        let n = count as f64;
+        let rmse = (sum_sqr / n).sqrt();
+        let range = max_ref - min_ref;
+        let psnr = if rmse == 0.0 {
+            f64::INFINITY
+        } else if range == 0.0 {
+            f64::NEG_INFINITY
+        } else {
+            20.0 * (range / rmse).log10()
+        };
+
+        Ok(DiffMetrics {
+            count,
+            rmse: rmse as f32,
+            mae: (sum_abs / n) as f32,
+            max_abs_error: max as f32,
+            bias: (sum / n) as f32,
+            psnr: psnr as f32,
+            ssim: self.ssim(reference, mask, min_ref, range) as f32,
+        })
+    }
+
+    /// Mean SSIM over the compared cells, with local statistics from a Gaussian window.
+    fn ssim(&self, reference: &Field, mask: Option<&Field>, min: f64, range: f64) -> f64 {
+        let kernel: Vec<f64> = {
+            let weights: Vec<f64> = (-SSIM_RADIUS..=SSIM_RADIUS)
+                .map(|k| (-((k * k) as f64) / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp())
+                .collect();
+            let total: f64 = weights.iter().sum();
+            weights.iter().map(|w| w / total).collect()
+        };
+
+        // SSIM is not shift invariant: like image intensities, heights start at 0 at the
+        // reference minimum. Unchecked cells outside the mask may be NaN, they read as 0.
+        let shifted = |field: &Field| -> Vec<f64> {
+            field
+                .flattened_field
+                .par_iter()
+                .map(|&v| if v.is_finite() { v as f64 - min } else { 0.0 })
+                .collect()
+        };
+        let x = shifted(self);
+        let y = shifted(reference);
+        let product = |a: &[f64], b: &[f64]| -> Vec<f64> {
+            a.par_iter().zip(b.par_iter()).map(|(p, q)| p * q).collect()
+        };
+
+        let width = self.width;
+        let mean_x = blur(&x, width, &kernel);
+        let mean_y = blur(&y, width, &kernel);
+        let xx = blur(&product(&x, &x), width, &kernel);
+        let yy = blur(&product(&y, &y), width, &kernel);
+        let xy = blur(&product(&x, &y), width, &kernel);
+
+        // a flat reference has no range, any scale keeps the constants from vanishing
+        let range = if range > 0.0 { range } else { 1.0 };
+        let c1 = (SSIM_K1 * range).powi(2);
+        let c2 = (SSIM_K2 * range).powi(2);
+
+        let (sum, count) = (0..x.len())
+            .into_par_iter()
+            .filter(|&i| Self::compared(mask, i))
+            .map(|i| {
+                let (mx, my) = (mean_x[i], mean_y[i]);
+                let vx = xx[i] - mx * mx;
+                let vy = yy[i] - my * my;
+                let cov = xy[i] - mx * my;
+                let ssim = ((2.0 * mx * my + c1) * (2.0 * cov + c2))
+                    / ((mx * mx + my * my + c1) * (vx + vy + c2));
+                (ssim, 1_usize)
+            })
+            .reduce(|| (0.0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+        sum / count.max(1) as f64
+    }
+
+    /// This field minus `reference`, 0 outside the mask.
+    pub fn signed_difference(
+        &self,
+        reference: &Field,
+        mask: Option<&Field>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.check_same_size(reference)?;
+        if let Some(mask) = mask {
+            self.check_same_size(mask)?;
+        }
+
+        let result: Vec<f32> = (0..self.flattened_field.len())
+            .into_par_iter()
+            .map(|i| {
+                if Self::compared(mask, i) {
+                    self.flattened_field[i] - reference.flattened_field[i]
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Diverging RGB8 image of this field minus `reference`: white where they agree, red
+    /// where this field is higher and blue where it is lower, saturating at `limit` (the
+    /// largest difference if `None`). Cells outside the mask are grey.
+    pub fn write_difference_png(
+        &self,
+        reference: &Field,
+        mask: Option<&Field>,
+        limit: Option<f32>,
+        path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(limit) = limit {
+            if !(limit.is_finite() && limit > 0.0) {
+                return Err(format!("Invalid difference limit: {}", limit).into());
+            }
+        }
+        let difference = self.signed_difference(reference, mask)?;
+        let limit = limit.unwrap_or_else(|| {
+            difference
+                .flattened_field
+                .iter()
+                .filter(|d| d.is_finite())
+                .fold(0.0_f32, |m, d| m.max(d.abs()))
+        });
+
+        let img = ImageBuffer::from_fn(self.width as u32, self.height() as u32, |x, y| {
+            let i = y as usize * self.width + x as usize;
+            if !Self::compared(mask, i) {
+                return Rgb([128, 128, 128]);
+            }
+            let d = difference.flattened_field[i];
+            let t = if limit > 0.0 && !d.is_nan() {
+                (d / limit).clamp(-1.0, 1.0)
+            } else {
+                0.0
+            };
+            let fade = (255.0 * (1.0 - t.abs())).round() as u8;
+            if t >= 0.0 {
+                Rgb([255, fade, fade])
+            } else {
+                Rgb([fade, fade, 255])
+            }
+        });
+        img.save(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp() -> Field {
+        Field::from_fn(16, 12, |x, y| (x as f32 * 0.3).sin() + y as f32 * 0.1).unwrap()
+    }
+
+    #[test]
+    fn identical_fields_match_exactly() {
+        let field = ramp();
+        let metrics = field.compare(&field, None).unwrap();
+
+        assert_eq!(metrics.count, 16 * 12);
+        assert_eq!(metrics.rmse, 0.0);
+        assert_eq!(metrics.psnr, f32::INFINITY);
+        assert!((metrics.ssim - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn constant_offset() {
+        let reference = ramp();
+        let field = reference.map(|v| v + 0.5);
+        let metrics = field.compare(&reference, None).unwrap();
+
+        assert!((metrics.bias - 0.5).abs() < 1e-6);
+        assert!((metrics.mae - 0.5).abs() < 1e-6);
+        assert!((metrics.rmse - 0.5).abs() < 1e-6);
+        assert!((metrics.max_abs_error - 0.5).abs() < 1e-6);
+
+        let lower = reference
+            .map(|v| v - 0.5)
+            .compare(&reference, None)
+            .unwrap();
+        assert!((lower.bias + 0.5).abs() < 1e-6);
+        assert_eq!(lower.psnr, metrics.psnr);
+    }
+
+    #[test]
+    fn masked_out_cells_change_nothing() {
+        let reference = ramp();
+        let mask = Field::from_fn(16, 12, |x, _| if x < 8 { 1.0 } else { 0.0 }).unwrap();
+        let offset = Field::from_fn(16, 12, |x, _| if x < 8 { 0.25 } else { 100.0 }).unwrap();
+        let field = reference.zip_with(&offset, |a, b| a + b).unwrap();
+
+        let masked = field.compare(&reference, Some(&mask)).unwrap();
+        assert_eq!(masked.count, 8 * 12);
+        assert!((masked.max_abs_error - 0.25).abs() < 1e-6);
+        assert!((masked.bias - 0.25).abs() < 1e-6);
+
+        let difference = field.signed_difference(&reference, Some(&mask)).unwrap();
+        assert!((0..12).all(|y| (8..16).all(|x| difference.get(x, y) == 0.0)));
+    }
+
+    #[test]
+    fn flat_reference_with_error() {
+        let reference = Field::from_fn(4, 4, |_, _| 1.0).unwrap();
+        let field = reference.map(|v| v + 0.1);
+        assert_eq!(
+            field.compare(&reference, None).unwrap().psnr,
+            f32::NEG_INFINITY
+        );
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let field = ramp();
+        let other = Field::from_fn(12, 16, |_, _| 0.0).unwrap();
+        assert!(field.compare(&other, None).is_err());
+        assert!(field.compare(&field, Some(&other)).is_err());
+        assert!(field.signed_difference(&other, None).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::field::field::Field;
+use crate::field::hydrology::D8_OFFSETS;
+
+const D4_OFFSETS: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// edge neighbours only
+    Four,
+    /// edge and corner neighbours
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &D4_OFFSETS,
+            Connectivity::Eight => &D8_OFFSETS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub label: u32,
+    /// number of cells
+    pub area: usize,
+    /// inclusive bounding box, (min_x, min_y, max_x, max_y)
+    pub bounds: (usize, usize, usize, usize),
+    /// mean cell position
+    pub centroid: (f32, f32),
+}
+
+/// Component label per cell, 0 for background and 1.. in scan order of each component's
+/// first cell.
+pub struct Labels {
+    pub labels: Box<[u32]>,
+    pub width: usize,
+    pub components: Vec<Component>,
+}
+
+impl Labels {
+    pub fn height(&self) -> usize {
+        self.labels.len() / self.width
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        self.labels[y * self.width + x]
+    }
+
+    pub fn component(&self, label: u32) -> Option<&Component> {
+        label
+            .checked_sub(1)
+            .and_then(|i| self.components.get(i as usize))
+    }
+
+    /// labels as a field, e.g. for `write_png_u16`
+    pub fn to_field(&self) -> Field {
+        let values: Vec<f32> = self.labels.iter().map(|&l| l as f32).collect();
+
+        Field {
+            flattened_field: values.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+
+    /// 1.0 on the cells of `label`, 0.0 elsewhere
+    pub fn mask(&self, label: u32) -> Field {
+        let values: Vec<f32> = self
+            .labels
+            .iter()
+            .map(|&l| (l == label && l != 0) as u8 as f32)
+            .collect();
+
+        Field {
+            flattened_field: values.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+
+    /// Drops components smaller than `min_area` into the background and renumbers the rest
+    /// in their original order.
+    pub fn without_smaller_than(&self, min_area: usize) -> Self {
+        let mut renumbered = vec![0_u32; self.components.len() + 1];
+        let mut components = Vec::new();
+        for component in self.components.iter().filter(|c| c.area >= min_area) {
+            let label = components.len() as u32 + 1;
+            renumbered[component.label as usize] = label;
+            components.push(Component {
+                label,
+                ..component.clone()
+            });
+        }
+
+        Self {
+            labels: self
+                .labels
+                .iter()
+                .map(|&l| renumbered[l as usize])
+                .collect(),
+            width: self.width,
+            components,
+        }
+    }
+}
+
+impl Field {
+    /// Labels the connected regions of non-zero cells in this mask.
+    pub fn label_components(&self, connectivity: Connectivity) -> Labels {
+        let width = self.width;
+        let mut labels = vec![0_u32; self.flattened_field.len()].into_boxed_slice();
+        let mut components = Vec::new();
+        let mut stack = Vec::new();
+
+        for start in 0..self.flattened_field.len() {
+            if self.flattened_field[start] == 0.0 || labels[start] != 0 {
+                continue;
+            }
+
+            let label = components.len() as u32 + 1;
+            let mut area = 0;
+            let (mut sum_x, mut sum_y) = (0.0_f64, 0.0_f64);
+            let mut bounds = (usize::MAX, usize::MAX, 0, 0);
+
+            labels[start] = label;
+            stack.push(start);
+            while let Some(i) = stack.pop() {
+                let (x, y) = (i % width, i / width);
+                area += 1;
+                sum_x += x as f64;
+                sum_y += y as f64;
+                bounds = (
+                    bounds.0.min(x),
+                    bounds.1.min(y),
+                    bounds.2.max(x),
+                    bounds.3.max(y),
+                );
+
+                for &offset in connectivity.offsets() {
+                    if let Some(n) = self.neighbor(i, offset) {
+                        if self.flattened_field[n] != 0.0 && labels[n] == 0 {
+                            labels[n] = label;
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+
+            components.push(Component {
+                label,
+                area,
+                bounds,
+                centroid: ((sum_x / area as f64) as f32, (sum_y / area as f64) as f32),
+            });
+        }
+
+        Labels {
+            labels,
+            width,
+            components,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(rows: &[&str]) -> Field {
+        let values: Vec<f32> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| (c == '#') as u8 as f32))
+            .collect();
+
+        Field {
+            flattened_field: values.into_boxed_slice(),
+            width: rows[0].len(),
+        }
+    }
+
+    #[test]
+    fn four_and_eight_connectivity() {
+        let field = mask(&["##..#", "..#.#", "...#.", "#...."]);
+
+        let four = field.label_components(Connectivity::Four);
+        let expected_four = [
+            1, 1, 0, 0, 2, //
+            0, 0, 3, 0, 2, //
+            0, 0, 0, 4, 0, //
+            5, 0, 0, 0, 0,
+        ];
+        assert_eq!(&*four.labels, &expected_four);
+        assert_eq!(four.components.len(), 5);
+
+        // the diagonal steps join everything but the lone cell bottom left
+        let eight = field.label_components(Connectivity::Eight);
+        let expected_eight = [
+            1, 1, 0, 0, 1, //
+            0, 0, 1, 0, 1, //
+            0, 0, 0, 1, 0, //
+            2, 0, 0, 0, 0,
+        ];
+        assert_eq!(&*eight.labels, &expected_eight);
+
+        let joined = eight.component(1).unwrap();
+        assert_eq!((joined.area, joined.bounds), (6, (0, 0, 4, 2)));
+        assert_eq!(eight.component(2).unwrap().centroid, (0.0, 3.0));
+        assert!(eight.component(0).is_none());
+
+        let large = eight.without_smaller_than(2);
+        assert_eq!(large.components.len(), 1);
+        assert_eq!(large.get(0, 3), 0);
+    }
+}
@@ -0,0 +1,423 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+const NO_SEGMENT: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+pub struct Contour {
+    pub level: f32,
+    /// (x, y) in cell coordinates, closed contours repeat their first point at the end
+    pub points: Vec<(f32, f32)>,
+    pub closed: bool,
+    /// index (major) contour, drawn heavier on maps
+    pub index: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContourOptions {
+    /// rounds of Chaikin corner cutting applied to every line
+    pub smoothing_iterations: usize,
+    /// levels that are a multiple of this are tagged as index contours
+    pub index_interval: Option<f32>,
+}
+
+impl Field {
+    /// Multiples of `interval` strictly inside the value range of the field.
+    pub fn contour_levels(&self, interval: f32) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        if !(interval > 0.0 && interval.is_finite()) {
+            return Err(format!("Invalid contour interval: {}", interval).into());
+        }
+
+        let (min, max) = self
+            .flattened_field
+            .iter()
+            .filter(|v| !v.is_nan())
+            .fold((f32::MAX, f32::MIN), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+        if min > max {
+            return Ok(Vec::new());
+        }
+
+        let first = (min / interval).floor() as i64 + 1;
+        let last = (max / interval).ceil() as i64 - 1;
+        Ok((first..=last).map(|k| k as f32 * interval).collect())
+    }
+
+    pub fn contours_at_interval(
+        &self,
+        interval: f32,
+        options: &ContourOptions,
+    ) -> Result<Vec<Contour>, Box<dyn std::error::Error>> {
+        let levels = self.contour_levels(interval)?;
+        self.contours(&levels, options)
+    }
+
+    /// Marching squares isolines for each level, stitched into polylines.
+    ///
+    /// Saddle cells are resolved with the mean of their corners, and cells touching a NaN
+    /// are skipped so contours stop at nodata.
+    pub fn contours(
+        &self,
+        levels: &[f32],
+        options: &ContourOptions,
+    ) -> Result<Vec<Contour>, Box<dyn std::error::Error>> {
+        if self.width < 2 || self.height() < 2 {
+            return Err("Contouring needs a field of at least 2x2 cells".into());
+        }
+        if let Some(level) = levels.iter().find(|l| !l.is_finite()) {
+            return Err(format!("Invalid contour level: {}", level).into());
+        }
+
+        let contours = levels
+            .par_iter()
+            .flat_map_iter(|&level| {
+                let index = options.index_interval.is_some_and(|interval| {
+                    let k = (level / interval).round();
+                    (level - k * interval).abs() <= interval * 1e-4
+                });
+
+                self.isolines(level)
+                    .into_iter()
+                    .map(move |(points, closed)| {
+                        let points = if closed {
+                            chaikin_closed(points, options.smoothing_iterations)
+                        } else {
+                            chaikin_open(points, options.smoothing_iterations)
+                        };
+                        Contour {
+                            level,
+                            points,
+                            closed,
+                            index,
+                        }
+                    })
+            })
+            .collect();
+
+        Ok(contours)
+    }
+
+    /// Edge keys: 2 * cell for the horizontal edge to the right of the cell,
+    /// 2 * cell + 1 for the vertical edge below it.
+    fn edge_point(&self, key: usize, level: f32) -> (f32, f32) {
+        let cell = key / 2;
+        let x = cell % self.width;
+        let y = cell / self.width;
+        let a = self.flattened_field[cell];
+        let b = if key.is_multiple_of(2) {
+            self.get(x + 1, y)
+        } else {
+            self.get(x, y + 1)
+        };
+
+        let t = if (b - a).abs() > f32::EPSILON {
+            ((level - a) / (b - a)).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+
+        if key.is_multiple_of(2) {
+            (x as f32 + t, y as f32)
+        } else {
+            (x as f32, y as f32 + t)
+        }
+    }
+
+    fn isolines(&self, level: f32) -> Vec<(Vec<(f32, f32)>, bool)> {
+        let mut segments: Vec<(usize, usize)> = Vec::new();
+
+        for y in 0..self.height() - 1 {
+            for x in 0..self.width - 1 {
+                let tl = self.get(x, y);
+                let tr = self.get(x + 1, y);
+                let br = self.get(x + 1, y + 1);
+                let bl = self.get(x, y + 1);
+                if tl.is_nan() || tr.is_nan() || br.is_nan() || bl.is_nan() {
+                    continue;
+                }
+
+                let top = 2 * (y * self.width + x);
+                let left = top + 1;
+                let right = 2 * (y * self.width + x + 1) + 1;
+                let bottom = 2 * ((y + 1) * self.width + x);
+
+                let case = (tl >= level) as u8
+                    | ((tr >= level) as u8) << 1
+                    | ((br >= level) as u8) << 2
+                    | ((bl >= level) as u8) << 3;
+
+                match case {
+                    0 | 15 => {}
+                    1 | 14 => segments.push((left, top)),
+                    2 | 13 => segments.push((top, right)),
+                    3 | 12 => segments.push((left, right)),
+                    4 | 11 => segments.push((right, bottom)),
+                    6 | 9 => segments.push((top, bottom)),
+                    7 | 8 => segments.push((left, bottom)),
+                    5 | 10 => {
+                        let center_above = (tl + tr + br + bl) / 4.0 >= level;
+                        // tl and br on one side, tr and bl on the other
+                        if (case == 5) == center_above {
+                            segments.push((left, bottom));
+                            segments.push((top, right));
+                        } else {
+                            segments.push((left, top));
+                            segments.push((right, bottom));
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        // every edge is shared by at most two cells
+        let mut at_edge: HashMap<usize, [usize; 2]> = HashMap::new();
+        for (s, &(a, b)) in segments.iter().enumerate() {
+            for key in [a, b] {
+                let slot = at_edge.entry(key).or_insert([NO_SEGMENT; 2]);
+                if slot[0] == NO_SEGMENT {
+                    slot[0] = s;
+                } else {
+                    slot[1] = s;
+                }
+            }
+        }
+
+        let mut used = vec![false; segments.len()];
+        let mut lines = Vec::new();
+
+        let mut walk = |start: usize, key: usize, used: &mut Vec<bool>| {
+            let mut keys = vec![key];
+            let mut segment = start;
+            let mut key = key;
+            loop {
+                used[segment] = true;
+                let (a, b) = segments[segment];
+                key = if a == key { b } else { a };
+                keys.push(key);
+
+                match at_edge[&key].iter().find(|&&s| s != NO_SEGMENT && !used[s]) {
+                    Some(&next) => segment = next,
+                    None => break,
+                }
+            }
+            let closed = keys.len() > 2 && keys.first() == keys.last();
+            let points = keys.iter().map(|&k| self.edge_point(k, level)).collect();
+            lines.push((points, closed));
+        };
+
+        // open lines start at edges only one segment touches
+        for s in 0..segments.len() {
+            if used[s] {
+                continue;
+            }
+            let (a, b) = segments[s];
+            if at_edge[&a][1] == NO_SEGMENT {
+                walk(s, a, &mut used);
+            } else if at_edge[&b][1] == NO_SEGMENT {
+                walk(s, b, &mut used);
+            }
+        }
+        for s in 0..segments.len() {
+            if !used[s] {
+                walk(s, segments[s].0, &mut used);
+            }
+        }
+
+        lines
+    }
+}
+
+fn chaikin_open(mut points: Vec<(f32, f32)>, iterations: usize) -> Vec<(f32, f32)> {
+    for _ in 0..iterations {
+        if points.len() < 3 {
+            break;
+        }
+        let mut smoothed = Vec::with_capacity(points.len() * 2);
+        smoothed.push(points[0]);
+        for pair in points.windows(2) {
+            let (p, q) = (pair[0], pair[1]);
+            smoothed.push((0.75 * p.0 + 0.25 * q.0, 0.75 * p.1 + 0.25 * q.1));
+            smoothed.push((0.25 * p.0 + 0.75 * q.0, 0.25 * p.1 + 0.75 * q.1));
+        }
+        smoothed.push(points[points.len() - 1]);
+        points = smoothed;
+    }
+    points
+}
+
+fn chaikin_closed(mut points: Vec<(f32, f32)>, iterations: usize) -> Vec<(f32, f32)> {
+    for _ in 0..iterations {
+        if points.len() < 4 {
+            break;
+        }
+        // the last point duplicates the first
+        let mut smoothed = Vec::with_capacity(points.len() * 2);
+        for pair in points.windows(2) {
+            let (p, q) = (pair[0], pair[1]);
+            smoothed.push((0.75 * p.0 + 0.25 * q.0, 0.75 * p.1 + 0.25 * q.1));
+            smoothed.push((0.25 * p.0 + 0.75 * q.0, 0.25 * p.1 + 0.75 * q.1));
+        }
+        smoothed.push(smoothed[0]);
+        points = smoothed;
+    }
+    points
+}
+
+/// GeoJSON FeatureCollection of LineStrings in cell coordinates (x right, y down).
+pub fn write_contours_geojson(
+    contours: &[Contour],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    write!(out, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+    for (i, contour) in contours.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(
+            out,
+            "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"LineString\",\"coordinates\":["
+        )?;
+        for (j, (x, y)) in contour.points.iter().enumerate() {
+            if j > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "[{},{}]", x, y)?;
+        }
+        write!(
+            out,
+            "]}},\"properties\":{{\"level\":{},\"closed\":{},\"index\":{}}}}}",
+            contour.level, contour.closed, contour.index
+        )?;
+    }
+    writeln!(out, "]}}")?;
+
+    out.flush()?;
+    Ok(())
+}
+
+/// SVG of the contours over a `width` x `height` cell canvas, index contours drawn heavier.
+pub fn write_contours_svg(
+    contours: &[Contour],
+    width: usize,
+    height: usize,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    )?;
+    writeln!(
+        out,
+        "<g fill=\"none\" stroke=\"#6b4423\" stroke-linejoin=\"round\" stroke-linecap=\"round\">"
+    )?;
+    for contour in contours {
+        if contour.points.len() < 2 {
+            continue;
+        }
+        write!(
+            out,
+            "<path class=\"{}\" data-level=\"{}\" stroke-width=\"{}\" d=\"",
+            if contour.index {
+                "index"
+            } else {
+                "intermediate"
+            },
+            contour.level,
+            if contour.index { 1.0 } else { 0.5 }
+        )?;
+        for (j, (x, y)) in contour.points.iter().enumerate() {
+            write!(out, "{}{:.3} {:.3}", if j == 0 { "M" } else { " L" }, x, y)?;
+        }
+        if contour.closed {
+            write!(out, " Z")?;
+        }
+        writeln!(out, "\"/>")?;
+    }
+    writeln!(out, "</g>")?;
+    writeln!(out, "</svg>")?;
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each contour's end points, rounded and in a fixed order.
+    fn ends(contours: &[Contour]) -> Vec<[(i32, i32); 2]> {
+        let round = |(x, y): (f32, f32)| ((x * 100.0).round() as i32, (y * 100.0).round() as i32);
+        let mut ends: Vec<[(i32, i32); 2]> = contours
+            .iter()
+            .map(|c| {
+                let mut pair = [round(c.points[0]), round(c.points[c.points.len() - 1])];
+                pair.sort();
+                pair
+            })
+            .collect();
+        ends.sort();
+        ends
+    }
+
+    #[test]
+    fn cone_gives_closed_rings() {
+        let cone = Field::from_fn(21, 21, |x, y| {
+            10.0 - (x as f32 - 10.0).hypot(y as f32 - 10.0)
+        })
+        .unwrap();
+        let contours = cone
+            .contours(&[2.5, 5.5, 8.5], &ContourOptions::default())
+            .unwrap();
+
+        assert_eq!(contours.len(), 3);
+        for contour in &contours {
+            assert!(contour.closed);
+            assert_eq!(contour.points.first(), contour.points.last());
+            let radius = 10.0 - contour.level;
+            for &(x, y) in &contour.points {
+                let r = (x - 10.0).hypot(y - 10.0);
+                assert!((r - radius).abs() < 0.1 * radius, "radius {}", r);
+            }
+        }
+    }
+
+    #[test]
+    fn saddle_follows_the_cell_mean() {
+        // high corners top-left and bottom-right, the cell mean is 0.5
+        let saddle = Field {
+            flattened_field: vec![1.0, 0.0, 0.0, 1.0].into_boxed_slice(),
+            width: 2,
+        };
+        let options = ContourOptions::default();
+
+        // centre above the level: the high corners join and the low ones are cut off
+        let below_mean = saddle.contours(&[0.4], &options).unwrap();
+        assert_eq!(
+            ends(&below_mean),
+            vec![[(0, 60), (40, 100)], [(60, 0), (100, 40)]]
+        );
+
+        // centre below the level: the low corners join and the high ones are cut off
+        let above_mean = saddle.contours(&[0.6], &options).unwrap();
+        assert_eq!(
+            ends(&above_mean),
+            vec![[(0, 40), (40, 0)], [(60, 100), (100, 60)]]
+        );
+        assert!(above_mean.iter().all(|c| !c.closed && c.points.len() == 2));
+    }
+}
@@ -0,0 +1,314 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::hydrology::D8_OFFSETS;
+use crate::field::view::Raster;
+
+const UNVISITED: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CriticalPointKind {
+    Peak,
+    Pit,
+    Saddle,
+}
+
+impl CriticalPointKind {
+    fn name(self) -> &'static str {
+        match self {
+            CriticalPointKind::Peak => "peak",
+            CriticalPointKind::Pit => "pit",
+            CriticalPointKind::Saddle => "saddle",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CriticalPoint {
+    pub kind: CriticalPointKind,
+    pub x: usize,
+    pub y: usize,
+    pub height: f32,
+    /// peaks: drop to the key col, pits: rise to the key col
+    pub prominence: Option<f32>,
+    /// saddle that connects a peak (pit) to higher (lower) ground
+    pub key_col: Option<(usize, usize)>,
+    /// distance in cells to the nearest more extreme cell
+    pub isolation: Option<f32>,
+}
+
+/// Result of a single prominence sweep, one entry per extremum.
+struct Extremum {
+    index: usize,
+    prominence: f32,
+    key_col: Option<usize>,
+}
+
+fn find(parent: &mut [u32], mut i: u32) -> u32 {
+    while parent[i as usize] != i {
+        // path halving
+        parent[i as usize] = parent[parent[i as usize] as usize];
+        i = parent[i as usize];
+    }
+    i
+}
+
+impl Field {
+    /// Cells sorted most extreme first, plus each cell's position in that order.
+    /// Ties are broken by index so plateaus still have exactly one extremum.
+    fn ranked_order(&self, descending: bool) -> (Vec<u32>, Vec<u32>) {
+        let mut order: Vec<u32> = (0..self.flattened_field.len() as u32).collect();
+        order.par_sort_unstable_by(|&a, &b| {
+            let by_height = self.flattened_field[a as usize]
+                .total_cmp(&self.flattened_field[b as usize])
+                .then(a.cmp(&b));
+            if descending {
+                by_height.reverse()
+            } else {
+                by_height
+            }
+        });
+
+        let mut rank = vec![0_u32; order.len()];
+        for (position, &i) in order.iter().enumerate() {
+            rank[i as usize] = position as u32;
+        }
+
+        (order, rank)
+    }
+
+    /// Union-find sweep over the cells in `order`. Every cell without an already visited
+    /// neighbour starts a component; when components meet, all but the one holding the
+    /// most extreme peak die and their peaks get the meeting cell as key col.
+    fn prominence_sweep(&self, order: &[u32], rank: &[u32]) -> Vec<Extremum> {
+        let mut parent = vec![UNVISITED; order.len()];
+        let mut summit = vec![0_u32; order.len()];
+        let mut extrema = Vec::new();
+        let mut roots = Vec::with_capacity(8);
+
+        for &c in order {
+            parent[c as usize] = c;
+            summit[c as usize] = c;
+
+            roots.clear();
+            for offset in D8_OFFSETS {
+                if let Some(n) = self.neighbor(c as usize, offset) {
+                    if parent[n] != UNVISITED {
+                        let root = find(&mut parent, n as u32);
+                        if !roots.contains(&root) {
+                            roots.push(root);
+                        }
+                    }
+                }
+            }
+
+            let Some(&best) = roots
+                .iter()
+                .min_by_key(|&&r| rank[summit[r as usize] as usize])
+            else {
+                continue;
+            };
+
+            for &root in roots.iter().filter(|&&r| r != best) {
+                let peak = summit[root as usize] as usize;
+                extrema.push(Extremum {
+                    index: peak,
+                    prominence: (self.flattened_field[peak] - self.flattened_field[c as usize])
+                        .abs(),
+                    key_col: Some(c as usize),
+                });
+                parent[root as usize] = best;
+            }
+            parent[c as usize] = best;
+        }
+
+        // whatever survives is measured against the opposite extreme of the field
+        if let Some(&last) = order.last() {
+            for (c, &p) in parent.iter().enumerate() {
+                if p as usize == c {
+                    let peak = summit[c] as usize;
+                    extrema.push(Extremum {
+                        index: peak,
+                        prominence: (self.flattened_field[peak]
+                            - self.flattened_field[last as usize])
+                            .abs(),
+                        key_col: None,
+                    });
+                }
+            }
+        }
+
+        extrema
+    }
+
+    /// Distance in cells to the nearest cell ranked more extreme than `i`,
+    /// `None` for the single most extreme cell.
+    fn isolation(&self, i: usize, rank: &[u32]) -> Option<f32> {
+        let x = (i % self.width) as isize;
+        let y = (i / self.width) as isize;
+        let width = self.width as isize;
+        let height = self.height() as isize;
+        let max_ring = width.max(height);
+
+        let mut best = f32::INFINITY;
+        for r in 1..max_ring {
+            if r as f32 > best {
+                break;
+            }
+
+            for py in (y - r).max(0)..=(y + r).min(height - 1) {
+                let on_edge_row = (py - y).abs() == r;
+                let step = if on_edge_row { 1 } else { 2 * r };
+                let mut px = x - r;
+                while px <= x + r {
+                    if px >= 0 && px < width {
+                        let n = (py * width + px) as usize;
+                        if rank[n] < rank[i] {
+                            let d = (((px - x).pow(2) + (py - y).pow(2)) as f32).sqrt();
+                            best = best.min(d);
+                        }
+                    }
+                    px += step;
+                }
+            }
+        }
+
+        best.is_finite().then_some(best)
+    }
+
+    /// Peaks and pits with at least `min_prominence`, together with their key cols as saddles.
+    ///
+    /// Prominence comes from a union-find sweep over the sorted heights (descending for peaks,
+    /// ascending for pits). The highest peak and lowest pit have no key col and are measured
+    /// against the opposite extreme of the field. Coordinates are in cells.
+    pub fn critical_points(
+        &self,
+        min_prominence: f32,
+    ) -> Result<Vec<CriticalPoint>, Box<dyn std::error::Error>> {
+        if min_prominence.is_nan() || min_prominence < 0.0 {
+            return Err(format!("Invalid minimum prominence: {}", min_prominence).into());
+        }
+
+        let mut points = Vec::new();
+        let mut saddles = Vec::new();
+
+        for (kind, descending) in [
+            (CriticalPointKind::Peak, true),
+            (CriticalPointKind::Pit, false),
+        ] {
+            let (order, rank) = self.ranked_order(descending);
+            let extrema: Vec<Extremum> = self
+                .prominence_sweep(&order, &rank)
+                .into_iter()
+                .filter(|e| e.prominence >= min_prominence)
+                .collect();
+
+            let found: Vec<CriticalPoint> = extrema
+                .par_iter()
+                .map(|e| CriticalPoint {
+                    kind,
+                    x: e.index % self.width,
+                    y: e.index / self.width,
+                    height: self.flattened_field[e.index],
+                    prominence: Some(e.prominence),
+                    key_col: e.key_col.map(|c| (c % self.width, c / self.width)),
+                    isolation: self.isolation(e.index, &rank),
+                })
+                .collect();
+
+            saddles.extend(extrema.iter().filter_map(|e| e.key_col));
+            points.extend(found);
+        }
+
+        saddles.sort_unstable();
+        saddles.dedup();
+        points.extend(saddles.into_iter().map(|c| CriticalPoint {
+            kind: CriticalPointKind::Saddle,
+            x: c % self.width,
+            y: c / self.width,
+            height: self.flattened_field[c],
+            prominence: None,
+            key_col: None,
+            isolation: None,
+        }));
+
+        Ok(points)
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+pub fn write_critical_points_csv(
+    points: &[CriticalPoint],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    writeln!(
+        out,
+        "kind,x,y,height,prominence,key_col_x,key_col_y,isolation"
+    )?;
+    for p in points {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            p.kind.name(),
+            p.x,
+            p.y,
+            p.height,
+            optional(p.prominence),
+            optional(p.key_col.map(|c| c.0)),
+            optional(p.key_col.map(|c| c.1)),
+            optional(p.isolation),
+        )?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// GeoJSON FeatureCollection of points in cell coordinates (x right, y down).
+pub fn write_critical_points_geojson(
+    points: &[CriticalPoint],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    let json_number = |value: Option<f32>| match value {
+        Some(v) if v.is_finite() => v.to_string(),
+        _ => "null".to_string(),
+    };
+
+    write!(out, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+    for (i, p) in points.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        let key_col = match p.key_col {
+            Some((x, y)) => format!("[{},{}]", x, y),
+            None => "null".to_string(),
+        };
+        write!(
+            out,
+            "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Point\",\"coordinates\":[{},{}]}},\
+             \"properties\":{{\"kind\":\"{}\",\"height\":{},\"prominence\":{},\"key_col\":{},\"isolation\":{}}}}}",
+            p.x,
+            p.y,
+            p.kind.name(),
+            json_number(Some(p.height)),
+            json_number(p.prominence),
+            key_col,
+            json_number(p.isolation),
+        )?;
+    }
+    writeln!(out, "]}}")?;
+
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+/// Squared 1D distance transform of the sampled function `f` (Felzenszwalb & Huttenlocher
+/// 2012): lower envelope of the parabolas rooted at every sample.
+fn squared_distance_1d(f: &[f64], d: &mut [f64], v: &mut [usize], z: &mut [f64]) {
+    let n = f.len();
+    let intersect = |q: usize, p: usize| {
+        let (qf, pf) = (q as f64, p as f64);
+        ((f[q] + qf * qf) - (f[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+    };
+
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+    for q in 1..n {
+        let mut s = intersect(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    for (q, out) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - v[k] as f64;
+        *out = offset * offset + f[v[k]];
+    }
+}
+
+/// Runs the 1D transform along every row of `data` in place.
+fn transform_rows(data: &mut [f64], width: usize) {
+    data.par_chunks_mut(width).for_each_init(
+        || {
+            (
+                vec![0.0; width],
+                vec![0.0; width],
+                vec![0; width],
+                vec![0.0; width + 1],
+            )
+        },
+        |(f, d, v, z), row| {
+            f.copy_from_slice(row);
+            squared_distance_1d(f, d, v, z);
+            row.copy_from_slice(d);
+        },
+    );
+}
+
+fn transpose(data: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let mut result = vec![0.0; data.len()];
+    result
+        .par_chunks_mut(height)
+        .enumerate()
+        .for_each(|(x, column)| {
+            for (y, value) in column.iter_mut().enumerate() {
+                *value = data[y * width + x];
+            }
+        });
+    result
+}
+
+impl Field {
+    /// Stands in for infinity inside the transform. It is above any squared distance in
+    /// the field plus one more row or column of offsets, yet small enough that every sum
+    /// stays an exact integer in f64.
+    fn far(&self) -> f64 {
+        let (width, height) = (self.width as f64, self.height() as f64);
+        (width * width + height * height) * 4.0
+    }
+
+    /// Exact squared distances in cells to the nearest cell where `inside` holds, at least
+    /// `far()` when there is none.
+    fn squared_distance_to(&self, inside: impl Fn(f32) -> bool + Sync) -> Vec<f64> {
+        let width = self.width;
+        let height = self.height();
+        let far = self.far();
+
+        let mut data: Vec<f64> = self
+            .flattened_field
+            .par_iter()
+            .map(|&v| if inside(v) { 0.0 } else { far })
+            .collect();
+
+        transform_rows(&mut data, width);
+        let mut columns = transpose(&data, width, height);
+        transform_rows(&mut columns, height);
+        transpose(&columns, height, width)
+    }
+
+    /// Euclidean distance from every cell to the nearest non-zero cell of this mask, in
+    /// world units. Mask cells are 0, and everything is infinite if the mask is empty.
+    pub fn distance_transform(&self, cell_size: f32) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        let far = self.far();
+
+        let result: Vec<f32> = self
+            .squared_distance_to(|v| v != 0.0)
+            .into_par_iter()
+            .map(|d| {
+                if d >= far {
+                    f32::INFINITY
+                } else {
+                    d.sqrt() as f32 * cell_size
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Signed distance to the edge of this mask in world units, negative inside (non-zero
+    /// cells) and positive outside. The zero level runs along the cell edges between the two.
+    pub fn signed_distance(&self, cell_size: f32) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+
+        let far = self.far();
+        let to_inside = self.squared_distance_to(|v| v != 0.0);
+        let to_outside = self.squared_distance_to(|v| v == 0.0);
+
+        let result: Vec<f32> = to_inside
+            .par_iter()
+            .zip(to_outside.par_iter())
+            .map(|(&d_in, &d_out)| {
+                let (d, sign) = if d_in == 0.0 {
+                    (d_out, -1.0)
+                } else {
+                    (d_in, 1.0)
+                };
+                if d >= far {
+                    sign * f32::INFINITY
+                } else {
+                    sign * (d.sqrt() as f32 - 0.5) * cell_size
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// squared distance from every cell to the nearest non-zero cell, by trying them all
+    fn brute_force(field: &Field) -> Vec<Option<f64>> {
+        let width = field.width as isize;
+        let features: Vec<(isize, isize)> = (0..field.flattened_field.len())
+            .filter(|&i| field.flattened_field[i] != 0.0)
+            .map(|i| (i as isize % width, i as isize / width))
+            .collect();
+
+        (0..field.flattened_field.len() as isize)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                features
+                    .iter()
+                    .map(|&(fx, fy)| ((fx - x).pow(2) + (fy - y).pow(2)) as f64)
+                    .min_by(f64::total_cmp)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_squared_distances() {
+        let scattered = Field::from_fn(13, 9, |x, y| ((x * 7 + y * 3) % 17 == 0) as u8 as f32);
+        let corner = Field::from_fn(17, 5, |x, y| (x == 16 && y == 0) as u8 as f32);
+
+        for field in [scattered.unwrap(), corner.unwrap()] {
+            let squared = field.squared_distance_to(|v| v != 0.0);
+            let expected: Vec<f64> = brute_force(&field).into_iter().flatten().collect();
+            assert_eq!(squared, expected);
+
+            let distance = field.distance_transform(2.0).unwrap();
+            for (d, e) in distance.flattened_field.iter().zip(expected.iter()) {
+                assert_eq!(*d, e.sqrt() as f32 * 2.0);
+            }
+        }
+    }
+
+    #[test]
+    fn no_feature_cells() {
+        let field = Field::from_fn(6, 4, |_, _| 0.0).unwrap();
+        assert!(brute_force(&field).iter().all(Option::is_none));
+
+        let far = field.far();
+        assert!(field
+            .squared_distance_to(|v| v != 0.0)
+            .iter()
+            .all(|&d| d >= far));
+        assert!(field
+            .distance_transform(1.0)
+            .unwrap()
+            .flattened_field
+            .iter()
+            .all(|d| *d == f32::INFINITY));
+    }
+}
@@ -0,0 +1,532 @@
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::rng::SplitMix64;
+use crate::field::view::Raster;
+
+/// Parameters for particle based hydraulic erosion, after Beyer (2015).
+///
+/// Rates are fractions per step and heights are in field units, so capacities scale with
+/// the relief of the field.
+#[derive(Debug, Clone, Copy)]
+pub struct DropletErosion {
+    pub droplets: usize,
+    pub seed: u64,
+    /// droplets simulated against the same snapshot of the terrain. Larger batches run
+    /// better in parallel, smaller ones let droplets react to each other sooner
+    pub batch_size: usize,
+    pub max_lifetime: usize,
+    /// 0 follows the gradient exactly, 1 ignores it
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erosion_rate: f32,
+    pub deposition_rate: f32,
+    pub evaporation: f32,
+    pub gravity: f32,
+    /// in cells, erosion is spread over this disc to avoid digging single-cell pits
+    pub brush_radius: usize,
+    pub initial_water: f32,
+    pub initial_speed: f32,
+}
+
+impl Default for DropletErosion {
+    fn default() -> Self {
+        Self {
+            droplets: 100_000,
+            seed: 0,
+            batch_size: 1024,
+            max_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            brush_radius: 3,
+            initial_water: 1.0,
+            initial_speed: 1.0,
+        }
+    }
+}
+
+/// Parameters for grid based shallow water erosion with virtual pipes (Mei et al. 2007).
+#[derive(Debug, Clone, Copy)]
+pub struct ShallowWaterErosion {
+    pub iterations: usize,
+    pub time_step: f32,
+    pub cell_size: f32,
+    /// water depth added to every cell per unit time
+    pub rain_rate: f32,
+    /// fraction of the water lost per unit time
+    pub evaporation: f32,
+    pub gravity: f32,
+    /// sediment carried per unit of discharge (speed times depth) on a vertical face
+    pub sediment_capacity: f32,
+    pub dissolving_rate: f32,
+    pub deposition_rate: f32,
+}
+
+impl Default for ShallowWaterErosion {
+    fn default() -> Self {
+        Self {
+            iterations: 500,
+            time_step: 0.02,
+            cell_size: 1.0,
+            rain_rate: 0.01,
+            evaporation: 0.05,
+            gravity: 9.81,
+            sediment_capacity: 1.0,
+            dissolving_rate: 0.5,
+            deposition_rate: 1.0,
+        }
+    }
+}
+
+/// Eroded terrain plus masks for texturing.
+pub struct ErosionResult {
+    pub field: Field,
+    /// total material removed from each cell
+    pub erosion: Field,
+    /// total material laid down on each cell
+    pub deposition: Field,
+    /// how much water passed through each cell
+    pub flow: Field,
+}
+
+/// Height changes and water visits of one droplet, applied after its batch.
+#[derive(Default)]
+struct DropletTrace {
+    deltas: Vec<(usize, f32)>,
+    flow: Vec<(usize, f32)>,
+}
+
+impl Field {
+    fn check_rate(name: &str, value: f32, max: f32) -> Result<(), Box<dyn std::error::Error>> {
+        if value.is_nan() || value < 0.0 || value > max {
+            return Err(format!("Invalid {}: {}", name, value).into());
+        }
+        Ok(())
+    }
+
+    /// (dx, dy, weight) of the cells within `radius`, weights falling off linearly
+    fn erosion_brush(radius: usize) -> Vec<(isize, isize, f32)> {
+        let r = radius as isize;
+        let mut brush = Vec::new();
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance <= radius as f32 {
+                    brush.push((dx, dy, radius as f32 + 1.0 - distance));
+                }
+            }
+        }
+        brush
+    }
+
+    fn droplet(
+        &self,
+        params: &DropletErosion,
+        brush: &[(isize, isize, f32)],
+        index: u64,
+    ) -> DropletTrace {
+        let width = self.width;
+        let height = self.height();
+        let mut rng = SplitMix64::stream(params.seed, index);
+        let mut trace = DropletTrace::default();
+        // the droplet sees the batch snapshot plus its own changes
+        let mut own: HashMap<usize, f32> = HashMap::new();
+
+        let read = |own: &HashMap<usize, f32>, x: usize, y: usize| {
+            let i = y * width + x;
+            self.flattened_field[i] + own.get(&i).copied().unwrap_or(0.0)
+        };
+        // bilinear height and gradient inside the cell at (x, y)
+        let surface = |own: &HashMap<usize, f32>, px: f32, py: f32| {
+            let (x, y) = (px as usize, py as usize);
+            let (u, v) = (px - x as f32, py - y as f32);
+            let nw = read(own, x, y);
+            let ne = read(own, x + 1, y);
+            let sw = read(own, x, y + 1);
+            let se = read(own, x + 1, y + 1);
+
+            let gx = (ne - nw) * (1.0 - v) + (se - sw) * v;
+            let gy = (sw - nw) * (1.0 - u) + (se - ne) * u;
+            let h =
+                nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+            (h, gx, gy)
+        };
+
+        // spread bilinearly over the corners of the cell at (x, y)
+        let deposit = |own: &mut HashMap<usize, f32>,
+                       trace: &mut DropletTrace,
+                       px: f32,
+                       py: f32,
+                       amount: f32| {
+            let (x, y) = (px as usize, py as usize);
+            let (u, v) = (px - x as f32, py - y as f32);
+            for (i, w) in [
+                (y * width + x, (1.0 - u) * (1.0 - v)),
+                (y * width + x + 1, u * (1.0 - v)),
+                ((y + 1) * width + x, (1.0 - u) * v),
+                ((y + 1) * width + x + 1, u * v),
+            ] {
+                *own.entry(i).or_insert(0.0) += amount * w;
+                trace.deltas.push((i, amount * w));
+            }
+        };
+
+        let mut x = rng.next_f32() * (width - 1) as f32;
+        let mut y = rng.next_f32() * (height - 1) as f32;
+        let (mut dir_x, mut dir_y) = (0.0_f32, 0.0_f32);
+        let mut speed = params.initial_speed;
+        let mut water = params.initial_water;
+        let mut sediment = 0.0_f32;
+
+        for _ in 0..params.max_lifetime {
+            let (cx, cy) = (x as usize, y as usize);
+            let (h, gx, gy) = surface(&own, x, y);
+
+            dir_x = dir_x * params.inertia - gx * (1.0 - params.inertia);
+            dir_y = dir_y * params.inertia - gy * (1.0 - params.inertia);
+            let length = (dir_x * dir_x + dir_y * dir_y).sqrt();
+            if length <= f32::EPSILON {
+                break;
+            }
+            dir_x /= length;
+            dir_y /= length;
+
+            trace.flow.push((cy * width + cx, water));
+
+            let (nx, ny) = (x + dir_x, y + dir_y);
+            if nx < 0.0 || ny < 0.0 || nx >= (width - 1) as f32 || ny >= (height - 1) as f32 {
+                // whatever it carries flows off the field
+                sediment = 0.0;
+                break;
+            }
+
+            let dh = surface(&own, nx, ny).0 - h;
+            let capacity =
+                (-dh * speed * water * params.sediment_capacity).max(params.min_sediment_capacity);
+
+            if sediment > capacity || dh > 0.0 {
+                // uphill: fill the step behind us, otherwise drop the excess
+                let amount = if dh > 0.0 {
+                    dh.min(sediment)
+                } else {
+                    (sediment - capacity) * params.deposition_rate
+                };
+                sediment -= amount;
+                deposit(&mut own, &mut trace, x, y, amount);
+            } else {
+                let amount = ((capacity - sediment) * params.erosion_rate).min(-dh);
+
+                let cells: Vec<(usize, f32)> = brush
+                    .iter()
+                    .filter_map(|&(dx, dy, w)| {
+                        let bx = cx as isize + dx;
+                        let by = cy as isize + dy;
+                        (bx >= 0 && by >= 0 && bx < width as isize && by < height as isize)
+                            .then(|| (by as usize * width + bx as usize, w))
+                    })
+                    .collect();
+                let total: f32 = cells.iter().map(|c| c.1).sum();
+
+                for (i, w) in cells {
+                    let removed = amount * w / total;
+                    *own.entry(i).or_insert(0.0) -= removed;
+                    trace.deltas.push((i, -removed));
+                }
+                sediment += amount;
+            }
+
+            speed = (speed * speed - dh * params.gravity).max(0.0).sqrt();
+            water *= 1.0 - params.evaporation;
+            x = nx;
+            y = ny;
+        }
+
+        // a droplet that dries up or comes to rest drops its load where it is
+        if sediment > 0.0 {
+            deposit(&mut own, &mut trace, x, y, sediment);
+        }
+        trace
+    }
+
+    /// Particle hydraulic erosion. Droplets run in parallel batches against a snapshot of
+    /// the terrain and their changes are applied in droplet order, so the result only
+    /// depends on the parameters, not on the thread count.
+    pub fn erode_droplets(
+        &self,
+        params: &DropletErosion,
+    ) -> Result<ErosionResult, Box<dyn std::error::Error>> {
+        if self.width < 2 || self.height() < 2 {
+            return Err("Droplet erosion needs a field of at least 2x2".into());
+        }
+        if params.batch_size == 0 {
+            return Err("Droplet batch size must be at least one".into());
+        }
+        for (name, value) in [
+            ("inertia", params.inertia),
+            ("erosion rate", params.erosion_rate),
+            ("deposition rate", params.deposition_rate),
+            ("evaporation", params.evaporation),
+        ] {
+            Self::check_rate(name, value, 1.0)?;
+        }
+        for (name, value) in [
+            ("sediment capacity", params.sediment_capacity),
+            ("minimum sediment capacity", params.min_sediment_capacity),
+            ("gravity", params.gravity),
+            ("initial water", params.initial_water),
+            ("initial speed", params.initial_speed),
+        ] {
+            Self::check_rate(name, value, f32::MAX)?;
+        }
+
+        let brush = Self::erosion_brush(params.brush_radius);
+        let len = self.flattened_field.len();
+
+        let mut terrain = self.clone();
+        let mut erosion = vec![0.0_f32; len];
+        let mut deposition = vec![0.0_f32; len];
+        let mut flow = vec![0.0_f32; len];
+
+        let mut start = 0;
+        while start < params.droplets {
+            let end = (start + params.batch_size).min(params.droplets);
+            let traces: Vec<DropletTrace> = (start..end)
+                .into_par_iter()
+                .map(|index| terrain.droplet(params, &brush, index as u64))
+                .collect();
+
+            for trace in traces {
+                for (i, delta) in trace.deltas {
+                    terrain.flattened_field[i] += delta;
+                    if delta < 0.0 {
+                        erosion[i] -= delta;
+                    } else {
+                        deposition[i] += delta;
+                    }
+                }
+                for (i, water) in trace.flow {
+                    flow[i] += water;
+                }
+            }
+            start = end;
+        }
+
+        let as_field = |values: Vec<f32>| Field {
+            flattened_field: values.into_boxed_slice(),
+            width: self.width,
+        };
+
+        Ok(ErosionResult {
+            field: terrain,
+            erosion: as_field(erosion),
+            deposition: as_field(deposition),
+            flow: as_field(flow),
+        })
+    }
+
+    /// Grid based hydraulic erosion: rain fills a shallow water layer that flows through
+    /// virtual pipes between neighbours, dissolves terrain where it can carry more sediment
+    /// and deposits it where it slows down. Cheaper than droplets on large fields.
+    pub fn erode_shallow_water(
+        &self,
+        params: &ShallowWaterErosion,
+    ) -> Result<ErosionResult, Box<dyn std::error::Error>> {
+        Self::check_cell_size(params.cell_size)?;
+        if params.time_step.is_nan() || params.time_step <= 0.0 {
+            return Err(format!("Invalid time step: {}", params.time_step).into());
+        }
+        for (name, value) in [
+            ("rain rate", params.rain_rate),
+            ("gravity", params.gravity),
+            ("sediment capacity", params.sediment_capacity),
+        ] {
+            Self::check_rate(name, value, f32::MAX)?;
+        }
+        for (name, value) in [
+            ("evaporation", params.evaporation * params.time_step),
+            ("dissolving rate", params.dissolving_rate * params.time_step),
+            ("deposition rate", params.deposition_rate * params.time_step),
+        ] {
+            // all three are per unit time and must not overshoot within one step
+            Self::check_rate(name, value, 1.0)?;
+        }
+
+        let width = self.width;
+        let height = self.height();
+        let len = self.flattened_field.len();
+        let dt = params.time_step;
+        let l = params.cell_size;
+        // pipe cross section l * l over pipe length l
+        let pipe = dt * params.gravity * l;
+
+        let mut terrain = self.flattened_field.to_vec();
+        let mut water = vec![0.0_f32; len];
+        let mut sediment = vec![0.0_f32; len];
+        // outflow towards left, right, top, bottom
+        let mut flux = vec![[0.0_f32; 4]; len];
+        let mut velocity = vec![(0.0_f32, 0.0_f32); len];
+
+        let mut erosion = vec![0.0_f32; len];
+        let mut deposition = vec![0.0_f32; len];
+        let mut flow = vec![0.0_f32; len];
+
+        const DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+        // index of the reverse direction
+        const OPPOSITE: [usize; 4] = [1, 0, 3, 2];
+
+        for _ in 0..params.iterations {
+            water
+                .par_iter_mut()
+                .for_each(|d| *d += params.rain_rate * dt);
+
+            let new_flux: Vec<[f32; 4]> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let level = terrain[i] + water[i];
+                    let mut out = [0.0_f32; 4];
+                    for (k, &offset) in DIRECTIONS.iter().enumerate() {
+                        // closed boundary, nothing flows off the edge
+                        if let Some(n) = self.neighbor(i, offset) {
+                            let drop = level - terrain[n] - water[n];
+                            out[k] = (flux[i][k] + pipe * drop).max(0.0);
+                        }
+                    }
+
+                    // never drain more than the cell holds
+                    let total: f32 = out.iter().sum::<f32>() * dt;
+                    let volume = water[i] * l * l;
+                    if total > volume && total > 0.0 {
+                        let scale = volume / total;
+                        out.iter_mut().for_each(|f| *f *= scale);
+                    }
+                    out
+                })
+                .collect();
+            flux = new_flux;
+
+            // suspended sediment leaves with the same share of the water as the outflow,
+            // which keeps the total mass exact
+            let outflow_share = |i: usize, k: usize| {
+                let volume = water[i] * l * l;
+                if volume > 0.0 {
+                    flux[i][k] * dt / volume
+                } else {
+                    0.0
+                }
+            };
+            sediment = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let leaving: f32 = (0..4).map(|k| outflow_share(i, k)).sum();
+                    let mut s = sediment[i] * (1.0 - leaving);
+                    for (k, &offset) in DIRECTIONS.iter().enumerate() {
+                        if let Some(n) = self.neighbor(i, offset) {
+                            s += sediment[n] * outflow_share(n, OPPOSITE[k]);
+                        }
+                    }
+                    s
+                })
+                .collect();
+
+            let updated: Vec<(f32, (f32, f32))> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let mut inflow = [0.0_f32; 4];
+                    for (k, &offset) in DIRECTIONS.iter().enumerate() {
+                        if let Some(n) = self.neighbor(i, offset) {
+                            inflow[k] = flux[n][OPPOSITE[k]];
+                        }
+                    }
+                    let outflow = flux[i];
+
+                    let change = dt * (inflow.iter().sum::<f32>() - outflow.iter().sum::<f32>());
+                    let depth = (water[i] + change / (l * l)).max(0.0);
+                    let mean_depth = 0.5 * (water[i] + depth);
+
+                    let through_x = 0.5 * (inflow[0] - outflow[0] + outflow[1] - inflow[1]);
+                    let through_y = 0.5 * (inflow[2] - outflow[2] + outflow[3] - inflow[3]);
+                    let v = if mean_depth > 1e-6 {
+                        (through_x / (l * mean_depth), through_y / (l * mean_depth))
+                    } else {
+                        (0.0, 0.0)
+                    };
+                    (depth, v)
+                })
+                .collect();
+            for (i, (depth, v)) in updated.into_iter().enumerate() {
+                water[i] = depth;
+                velocity[i] = v;
+            }
+
+            // dissolve or deposit against the local carrying capacity
+            let exchanged: Vec<f32> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let x = (i % width) as isize;
+                    let y = (i / width) as isize;
+                    let sample = |x: isize, y: isize| {
+                        let x = x.clamp(0, width as isize - 1) as usize;
+                        let y = y.clamp(0, height as isize - 1) as usize;
+                        terrain[y * width + x]
+                    };
+                    let gx = (sample(x + 1, y) - sample(x - 1, y)) / (2.0 * l);
+                    let gy = (sample(x, y + 1) - sample(x, y - 1)) / (2.0 * l);
+                    let gradient = (gx * gx + gy * gy).sqrt();
+                    let sin_tilt = gradient / (1.0 + gradient * gradient).sqrt();
+
+                    let (vx, vy) = velocity[i];
+                    let speed = (vx * vx + vy * vy).sqrt();
+                    // scaled by discharge rather than speed alone, so a film of water
+                    // racing down a steep face cannot strip the whole slope
+                    let capacity = params.sediment_capacity * sin_tilt * speed * water[i];
+
+                    if capacity > sediment[i] {
+                        params.dissolving_rate * dt * (capacity - sediment[i])
+                    } else {
+                        -params.deposition_rate * dt * (sediment[i] - capacity)
+                    }
+                })
+                .collect();
+            for (i, amount) in exchanged.into_iter().enumerate() {
+                terrain[i] -= amount;
+                sediment[i] += amount;
+                if amount > 0.0 {
+                    erosion[i] += amount;
+                } else {
+                    deposition[i] -= amount;
+                }
+                let (vx, vy) = velocity[i];
+                flow[i] += water[i] * (vx * vx + vy * vy).sqrt() * dt;
+            }
+
+            water
+                .par_iter_mut()
+                .for_each(|d| *d *= 1.0 - params.evaporation * dt);
+        }
+
+        // whatever is still suspended settles where it is
+        for i in 0..len {
+            terrain[i] += sediment[i];
+            deposition[i] += sediment[i];
+        }
+
+        let as_field = |values: Vec<f32>| Field {
+            flattened_field: values.into_boxed_slice(),
+            width,
+        };
+
+        Ok(ErosionResult {
+            field: as_field(terrain),
+            erosion: as_field(erosion),
+            deposition: as_field(deposition),
+            flow: as_field(flow),
+        })
+    }
+}
@@ -0,0 +1,585 @@
+use std::borrow::Cow;
+
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::ops::Comparison;
+
+/// A derived-layer formula over named fields, e.g. `"height - blurred"` or
+/// `"if(slope > 0.6, 1, 0) * mask"`.
+///
+/// Supports numbers, field names, `+ - * / ^`, unary minus, parentheses, the comparisons
+/// `< <= > >= == !=` (yielding 1/0 masks) and the functions `abs sqrt exp ln sin cos floor
+/// ceil min max pow clamp if`. Field names are identifiers made of letters, digits and `_`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Variable(String),
+    Negate(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Compare(Comparison, Box<Expression>, Box<Expression>),
+    Call(String, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    const SYMBOLS: [&str; 14] = [
+        "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "^", "(", ")", ",",
+    ];
+
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap_or_default();
+
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+                .unwrap_or(rest.len());
+            // allow exponents like 1e-3
+            let end = match rest[end..].chars().next() {
+                Some('e') | Some('E') => {
+                    let exp = &rest[end + 1..];
+                    let sign = exp.starts_with(['+', '-']) as usize;
+                    let digits = exp[sign..]
+                        .find(|ch: char| !ch.is_ascii_digit())
+                        .unwrap_or(exp.len() - sign);
+                    if digits > 0 {
+                        end + 1 + sign + digits
+                    } else {
+                        end
+                    }
+                }
+                _ => end,
+            };
+            let number = rest[..end]
+                .parse::<f32>()
+                .map_err(|_| format!("Invalid number '{}'", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(format!("Unexpected character '{}' in expression", c).into());
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent over the token list, lowest precedence first.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek_symbol(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.peek_symbol() == Some(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at token {}", symbol, self.position).into())
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        let left = self.additive()?;
+
+        let op = match self.peek_symbol() {
+            Some("<") => Comparison::Less,
+            Some("<=") => Comparison::LessEqual,
+            Some(">") => Comparison::Greater,
+            Some(">=") => Comparison::GreaterEqual,
+            Some("==") => Comparison::Equal,
+            Some("!=") => Comparison::NotEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+
+        let right = self.additive()?;
+        Ok(Expression::Compare(op, Box::new(left), Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek_symbol() {
+                Some("+") => BinaryOp::Add,
+                Some("-") => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            left = Expression::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek_symbol() {
+                Some("*") => BinaryOp::Mul,
+                Some("/") => BinaryOp::Div,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            left = Expression::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        if self.peek_symbol() == Some("-") {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        let base = self.primary()?;
+        if self.peek_symbol() == Some("^") {
+            self.position += 1;
+            // right associative: a ^ b ^ c == a ^ (b ^ c)
+            let exponent = self.unary()?;
+            return Ok(Expression::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression, Box<dyn std::error::Error>> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("Unexpected end of expression")?;
+        self.position += 1;
+
+        match token {
+            Token::Number(n) => Ok(Expression::Number(n)),
+            Token::Ident(name) => {
+                if self.peek_symbol() != Some("(") {
+                    return Ok(Expression::Variable(name));
+                }
+                self.position += 1;
+
+                let mut args = Vec::new();
+                if self.peek_symbol() != Some(")") {
+                    loop {
+                        args.push(self.comparison()?);
+                        if self.peek_symbol() == Some(",") {
+                            self.position += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(")")?;
+                Ok(Expression::Call(name, args))
+            }
+            Token::Symbol("(") => {
+                let inner = self.comparison()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Symbol(s) => Err(format!("Unexpected '{}' in expression", s).into()),
+        }
+    }
+}
+
+/// Intermediate result, scalars stay scalars until they meet a field. Input fields are
+/// borrowed, only results of operations are allocated and then reused in place.
+enum Value<'a> {
+    Scalar(f32),
+    Field(Cow<'a, Field>),
+}
+
+/// `f` over every cell, in place when the field is an owned intermediate.
+fn map_cells<F>(field: Cow<'_, Field>, f: F) -> Field
+where
+    F: Fn(f32) -> f32 + Sync,
+{
+    match field {
+        Cow::Borrowed(field) => field.map(f),
+        Cow::Owned(mut field) => {
+            field
+                .flattened_field
+                .par_iter_mut()
+                .for_each(|v| *v = f(*v));
+            field
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    fn zip<F>(self, other: Value<'a>, f: F) -> Result<Value<'a>, Box<dyn std::error::Error>>
+    where
+        F: Fn(f32, f32) -> f32 + Sync,
+    {
+        let field = match (self, other) {
+            (Value::Scalar(a), Value::Scalar(b)) => return Ok(Value::Scalar(f(a, b))),
+            (Value::Field(a), Value::Scalar(b)) => map_cells(a, |v| f(v, b)),
+            (Value::Scalar(a), Value::Field(b)) => map_cells(b, |v| f(a, v)),
+            (Value::Field(a), Value::Field(b)) => {
+                a.check_same_size(&b)?;
+                match (a, b) {
+                    (Cow::Owned(mut a), b) => {
+                        a.flattened_field
+                            .par_iter_mut()
+                            .zip(b.flattened_field.par_iter())
+                            .for_each(|(x, &y)| *x = f(*x, y));
+                        a
+                    }
+                    (a, Cow::Owned(mut b)) => {
+                        b.flattened_field
+                            .par_iter_mut()
+                            .zip(a.flattened_field.par_iter())
+                            .for_each(|(y, &x)| *y = f(x, *y));
+                        b
+                    }
+                    (a, b) => a.zip_with(&b, f)?,
+                }
+            }
+        };
+        Ok(Value::Field(Cow::Owned(field)))
+    }
+
+    fn map<F>(self, f: F) -> Value<'a>
+    where
+        F: Fn(f32) -> f32 + Sync,
+    {
+        match self {
+            Value::Scalar(a) => Value::Scalar(f(a)),
+            Value::Field(a) => Value::Field(Cow::Owned(map_cells(a, f))),
+        }
+    }
+
+    fn into_field_like(self, shape: &Field) -> Cow<'a, Field> {
+        match self {
+            Value::Field(f) => f,
+            Value::Scalar(v) => Cow::Owned(shape.map(|_| v)),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+
+        let expression = parser.comparison()?;
+        if parser.position != parser.tokens.len() {
+            return Err(format!(
+                "Unexpected trailing input at token {} of '{}'",
+                parser.position, source
+            )
+            .into());
+        }
+        Ok(expression)
+    }
+
+    /// Names of all fields the expression reads.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_variables(&mut names);
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) => {}
+            Expression::Variable(name) => names.push(name),
+            Expression::Negate(inner) => inner.collect_variables(names),
+            Expression::Binary(_, a, b) | Expression::Compare(_, a, b) => {
+                a.collect_variables(names);
+                b.collect_variables(names);
+            }
+            Expression::Call(_, args) => args.iter().for_each(|a| a.collect_variables(names)),
+        }
+    }
+
+    /// Evaluates over `fields`, which must all be the same size. An expression that uses
+    /// no fields at all still produces a field of that size filled with the constant.
+    pub fn evaluate(
+        &self,
+        fields: &HashMap<&str, &Field>,
+    ) -> Result<Field, Box<dyn std::error::Error>> {
+        let mut sizes = fields.values();
+        let first = sizes
+            .next()
+            .ok_or("Expression needs at least one input field")?;
+        for field in sizes {
+            first.check_same_size(field)?;
+        }
+
+        match self.eval(fields)? {
+            Value::Field(field) => Ok(field.into_owned()),
+            Value::Scalar(v) => Ok(first.map(|_| v)),
+        }
+    }
+
+    fn eval<'a>(
+        &self,
+        fields: &HashMap<&str, &'a Field>,
+    ) -> Result<Value<'a>, Box<dyn std::error::Error>> {
+        match self {
+            Expression::Number(n) => Ok(Value::Scalar(*n)),
+            Expression::Variable(name) => fields
+                .get(name.as_str())
+                .map(|&f| Value::Field(Cow::Borrowed(f)))
+                .ok_or_else(|| format!("Unknown field '{}' in expression", name).into()),
+            Expression::Negate(inner) => Ok(inner.eval(fields)?.map(|v| -v)),
+            Expression::Binary(op, a, b) => {
+                let (a, b) = (a.eval(fields)?, b.eval(fields)?);
+                match op {
+                    BinaryOp::Add => a.zip(b, |x, y| x + y),
+                    BinaryOp::Sub => a.zip(b, |x, y| x - y),
+                    BinaryOp::Mul => a.zip(b, |x, y| x * y),
+                    BinaryOp::Div => a.zip(b, |x, y| x / y),
+                    BinaryOp::Pow => a.zip(b, f32::powf),
+                }
+            }
+            Expression::Compare(op, a, b) => {
+                let op = *op;
+                a.eval(fields)?
+                    .zip(b.eval(fields)?, move |x, y| op.test(x, y) as u8 as f32)
+            }
+            Expression::Call(name, args) => self.call(name, args, fields),
+        }
+    }
+
+    fn call<'a>(
+        &self,
+        name: &str,
+        args: &[Expression],
+        fields: &HashMap<&str, &'a Field>,
+    ) -> Result<Value<'a>, Box<dyn std::error::Error>> {
+        let arity = match name {
+            "abs" | "sqrt" | "exp" | "ln" | "sin" | "cos" | "floor" | "ceil" => 1,
+            "min" | "max" | "pow" => 2,
+            "clamp" | "if" => 3,
+            _ => return Err(format!("Unknown function '{}' in expression", name).into()),
+        };
+        if args.len() != arity {
+            return Err(format!(
+                "Function '{}' takes {} arguments, got {}",
+                name,
+                arity,
+                args.len()
+            )
+            .into());
+        }
+
+        let mut values = args
+            .iter()
+            .map(|a| a.eval(fields))
+            .collect::<Result<Vec<Value<'a>>, _>>()?
+            .into_iter();
+        let mut next = || values.next().expect("arity checked above");
+
+        Ok(match name {
+            "abs" => next().map(f32::abs),
+            "sqrt" => next().map(f32::sqrt),
+            "exp" => next().map(f32::exp),
+            "ln" => next().map(f32::ln),
+            "sin" => next().map(f32::sin),
+            "cos" => next().map(f32::cos),
+            "floor" => next().map(f32::floor),
+            "ceil" => next().map(f32::ceil),
+            "min" => next().zip(next(), f32::min)?,
+            "max" => next().zip(next(), f32::max)?,
+            "pow" => next().zip(next(), f32::powf)?,
+            "clamp" => {
+                let value = next();
+                let (low, high) = (next(), next());
+                value.zip(low, f32::max)?.zip(high, f32::min)?
+            }
+            "if" => {
+                // select instead of blending so inf/NaN in the unused branch can't leak through
+                let condition = next();
+                let (if_true, if_false) = (next(), next());
+                match (condition, if_true, if_false) {
+                    (Value::Scalar(c), a, b) => {
+                        if c != 0.0 {
+                            a
+                        } else {
+                            b
+                        }
+                    }
+                    (Value::Field(c), a, b) => {
+                        let a = a.into_field_like(&c);
+                        let b = b.into_field_like(&c);
+                        Value::Field(Cow::Owned(Field::select(&c, &a, &b)?))
+                    }
+                }
+            }
+            _ => unreachable!(),
+        })
+    }
+}
+
+impl Field {
+    /// Parses and evaluates `expression` over the named `fields`, see `Expression`.
+    pub fn evaluate(
+        expression: &str,
+        fields: &HashMap<&str, &Field>,
+    ) -> Result<Field, Box<dyn std::error::Error>> {
+        Expression::parse(expression)?.evaluate(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::view::Raster;
+
+    fn number(n: f32) -> Box<Expression> {
+        Box::new(Expression::Number(n))
+    }
+
+    fn inputs() -> (Field, Field) {
+        let a = Field::from_fn(5, 4, |x, y| x as f32 + 10.0 * y as f32).unwrap();
+        let b = Field::from_fn(5, 4, |x, _| x as f32 * 0.5).unwrap();
+        (a, b)
+    }
+
+    #[test]
+    fn precedence() {
+        // power binds tighter than unary minus, which binds tighter than * and +
+        let parsed = Expression::parse("1 + 2 * -3 ^ 2").unwrap();
+        let power = Expression::Binary(BinaryOp::Pow, number(3.0), number(2.0));
+        let product = Expression::Binary(
+            BinaryOp::Mul,
+            number(2.0),
+            Box::new(Expression::Negate(Box::new(power))),
+        );
+        assert_eq!(
+            parsed,
+            Expression::Binary(BinaryOp::Add, number(1.0), Box::new(product))
+        );
+
+        let (a, _) = inputs();
+        let fields = HashMap::from([("a", &a)]);
+        let value = |source| Field::evaluate(source, &fields).unwrap().get(0, 0);
+        assert_eq!(value("1 + 2 * -3 ^ 2"), -17.0);
+        assert_eq!(value("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(value("8 - 2 - 1"), 5.0);
+        assert_eq!(value("-(2 + 3) * 2"), -10.0);
+        assert_eq!(value("1 + 1 > 1"), 1.0);
+    }
+
+    #[test]
+    fn fields_and_functions() {
+        let (a, b) = inputs();
+        let fields = HashMap::from([("a", &a), ("b", &b)]);
+
+        let difference = Field::evaluate("a - b * 2", &fields).unwrap();
+        assert_eq!(difference.get(3, 2), 20.0);
+
+        let masked = Field::evaluate("if(a > 12, a, -1)", &fields).unwrap();
+        assert_eq!((masked.get(2, 1), masked.get(3, 1)), (-1.0, 13.0));
+
+        let clamped = Field::evaluate("clamp(a, 2, max(b, 3))", &fields).unwrap();
+        assert_eq!((clamped.get(0, 0), clamped.get(4, 3)), (2.0, 3.0));
+
+        assert_eq!(
+            Expression::parse("a * b + a").unwrap().variables(),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (a, _) = inputs();
+        let small = Field::from_fn(3, 3, |_, _| 0.0).unwrap();
+        let fields = HashMap::from([("a", &a)]);
+
+        assert!(Field::evaluate("a + c", &fields).is_err());
+        assert!(Field::evaluate("wobble(a)", &fields).is_err());
+        assert!(Field::evaluate("max(a)", &fields).is_err());
+        assert!(Expression::parse("a +").is_err());
+        assert!(Expression::parse("(a").is_err());
+        assert!(Expression::parse("a b").is_err());
+        assert!(Expression::parse("a $ 2").is_err());
+
+        let mismatched = HashMap::from([("a", &a), ("s", &small)]);
+        assert!(Field::evaluate("a", &mismatched).is_err());
+        assert!(Field::evaluate("a + s", &mismatched).is_err());
+    }
+
+    #[test]
+    fn owned_intermediates_are_reused() {
+        let (a, b) = inputs();
+        let buffer_of = |value: &Value| match value {
+            Value::Field(field) => field.flattened_field.as_ptr(),
+            Value::Scalar(_) => panic!("expected a field"),
+        };
+
+        // owned on the left, then on the right of a binary operation
+        let doubled = Value::Field(Cow::Owned(a.map(|v| v * 2.0)));
+        let buffer = buffer_of(&doubled);
+        let sum = doubled
+            .map(|v| v + 1.0)
+            .zip(Value::Field(Cow::Borrowed(&b)), |x, y| x + y)
+            .unwrap();
+        assert_eq!(buffer_of(&sum), buffer);
+        let difference = Value::Field(Cow::Borrowed(&a))
+            .zip(sum, |x, y| x - y)
+            .unwrap();
+        assert_eq!(buffer_of(&difference), buffer);
+
+        // borrowed inputs are read, never written
+        let Value::Field(difference) = difference else {
+            unreachable!()
+        };
+        assert_eq!(difference.get(1, 1), 11.0 - (22.0 + 1.0 + 0.5));
+        assert_eq!((a.get(1, 1), b.get(1, 1)), (11.0, 0.5));
+
+        // two borrowed inputs need a new buffer
+        let fresh = Value::Field(Cow::Borrowed(&a))
+            .zip(Value::Field(Cow::Borrowed(&b)), |x, y| x + y)
+            .unwrap();
+        assert_ne!(buffer_of(&fresh), a.flattened_field.as_ptr());
+        assert_ne!(buffer_of(&fresh), b.flattened_field.as_ptr());
+    }
+}
@@ -0,0 +1,612 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// e^(i·angle)
+    pub fn from_angle(angle: f64) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn scale(self, s: f64) -> Self {
+        Self::new(self.re * s, self.im * s)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+/// In-place forward transform of a power-of-two length, `twiddles[k]` = e^(-2πik/n).
+fn radix2(data: &mut [Complex], twiddles: &[Complex]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let half = length / 2;
+        let step = n / length;
+        for start in (0..n).step_by(length) {
+            for k in 0..half {
+                let u = data[start + k];
+                let v = data[start + k + half] * twiddles[k * step];
+                data[start + k] = u + v;
+                data[start + k + half] = u - v;
+            }
+        }
+        length *= 2;
+    }
+}
+
+fn twiddles(n: usize) -> Vec<Complex> {
+    (0..n / 2)
+        .map(|k| Complex::from_angle(-2.0 * PI * k as f64 / n as f64))
+        .collect()
+}
+
+/// Chirp-z state for lengths that are not a power of two (Bluestein 1970).
+struct Bluestein {
+    /// e^(-πik²/n)
+    chirp: Vec<Complex>,
+    /// transform of the conjugate chirp, wrapped to the padded length
+    kernel: Vec<Complex>,
+}
+
+/// Precomputed 1D transform of one length, shared by every row.
+struct Plan {
+    n: usize,
+    twiddles: Vec<Complex>,
+    bluestein: Option<Bluestein>,
+}
+
+impl Plan {
+    fn new(n: usize) -> Self {
+        if n.is_power_of_two() {
+            return Self {
+                n,
+                twiddles: twiddles(n),
+                bluestein: None,
+            };
+        }
+
+        let m = (2 * n - 1).next_power_of_two();
+        let twiddles = twiddles(m);
+        // k² mod 2n keeps the angle small enough to stay exact for long rows
+        let chirp: Vec<Complex> = (0..n)
+            .map(|k| {
+                let k2 = (k as u128 * k as u128 % (2 * n as u128)) as f64;
+                Complex::from_angle(-PI * k2 / n as f64)
+            })
+            .collect();
+        let mut kernel = vec![Complex::default(); m];
+        kernel[0] = chirp[0].conj();
+        for k in 1..n {
+            kernel[k] = chirp[k].conj();
+            kernel[m - k] = chirp[k].conj();
+        }
+        radix2(&mut kernel, &twiddles);
+
+        Self {
+            n,
+            twiddles,
+            bluestein: Some(Bluestein { chirp, kernel }),
+        }
+    }
+
+    fn forward(&self, data: &mut [Complex], scratch: &mut Vec<Complex>) {
+        let Some(bluestein) = &self.bluestein else {
+            radix2(data, &self.twiddles);
+            return;
+        };
+
+        let m = bluestein.kernel.len();
+        scratch.clear();
+        scratch.resize(m, Complex::default());
+        for k in 0..self.n {
+            scratch[k] = data[k] * bluestein.chirp[k];
+        }
+        radix2(scratch, &self.twiddles);
+        for (s, &b) in scratch.iter_mut().zip(bluestein.kernel.iter()) {
+            *s = (*s * b).conj();
+        }
+        // inverse through the conjugate of the forward transform
+        radix2(scratch, &self.twiddles);
+        for k in 0..self.n {
+            data[k] = scratch[k].conj().scale(1.0 / m as f64) * bluestein.chirp[k];
+        }
+    }
+
+    fn inverse(&self, data: &mut [Complex], scratch: &mut Vec<Complex>) {
+        data.iter_mut().for_each(|c| *c = c.conj());
+        self.forward(data, scratch);
+        let scale = 1.0 / self.n as f64;
+        data.iter_mut().for_each(|c| *c = c.conj().scale(scale));
+    }
+}
+
+fn transform_rows(data: &mut [Complex], width: usize, inverse: bool) {
+    let plan = Plan::new(width);
+    data.par_chunks_mut(width)
+        .for_each_init(Vec::new, |scratch, row| {
+            if inverse {
+                plan.inverse(row, scratch);
+            } else {
+                plan.forward(row, scratch);
+            }
+        });
+}
+
+fn transpose(data: &[Complex], width: usize, height: usize) -> Vec<Complex> {
+    let mut result = vec![Complex::default(); data.len()];
+    result
+        .par_chunks_mut(height)
+        .enumerate()
+        .for_each(|(x, column)| {
+            for (y, value) in column.iter_mut().enumerate() {
+                *value = data[y * width + x];
+            }
+        });
+    result
+}
+
+pub(crate) fn transform_2d(
+    data: &[Complex],
+    width: usize,
+    height: usize,
+    inverse: bool,
+) -> Vec<Complex> {
+    let mut rows = data.to_vec();
+    transform_rows(&mut rows, width, inverse);
+    let mut columns = transpose(&rows, width, height);
+    transform_rows(&mut columns, height, inverse);
+    transpose(&columns, height, width)
+}
+
+/// Signed frequency of bin `k` of an `n`-point transform, in cycles per cell.
+fn frequency(k: usize, n: usize) -> f64 {
+    if k <= n / 2 {
+        k as f64 / n as f64
+    } else {
+        k as f64 / n as f64 - 1.0
+    }
+}
+
+/// Taper applied before spectral analysis so the field's edges don't leak into every
+/// frequency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    /// flat middle with cosine tapers over this fraction of each side, 0 is rectangular and
+    /// 1 is Hann
+    Tukey(f32),
+}
+
+impl Window {
+    fn weights(self, n: usize) -> Vec<f64> {
+        let last = (n.max(2) - 1) as f64;
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / last;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * (2.0 * PI * t).cos(),
+                    Window::Tukey(alpha) => {
+                        let alpha = alpha as f64;
+                        let edge = t.min(1.0 - t);
+                        if alpha <= 0.0 || edge >= alpha / 2.0 {
+                            1.0
+                        } else {
+                            0.5 - 0.5 * (2.0 * PI * edge / alpha).cos()
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn check(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Window::Tukey(alpha) = self {
+            if alpha.is_nan() || !(0.0..=1.0).contains(&alpha) {
+                return Err(format!("Invalid Tukey window fraction: {}", alpha).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Frequency band kept by `Field::frequency_filter`, cutoffs in cycles per cell up to the
+/// Nyquist limit of 0.5.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrequencyFilter {
+    LowPass(f32),
+    HighPass(f32),
+    BandPass(f32, f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FilterOptions {
+    /// width of the raised-cosine transition around each cutoff, in cycles per cell; a hard
+    /// cutoff rings
+    pub rolloff: f32,
+    /// fraction of each dimension mirrored onto every side and tapered to the mean, so the
+    /// transform's wrap-around doesn't bleed one edge into the other
+    pub padding: f32,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        Self {
+            rolloff: 0.02,
+            padding: 0.125,
+        }
+    }
+}
+
+/// Complex 2D spectrum, DC at (0, 0) and negative frequencies in the upper half of each axis.
+pub struct Spectrum {
+    pub data: Box<[Complex]>,
+    pub width: usize,
+}
+
+impl Spectrum {
+    pub fn height(&self) -> usize {
+        self.data.len() / self.width
+    }
+
+    pub fn get(&self, u: usize, v: usize) -> Complex {
+        self.data[v * self.width + u]
+    }
+
+    /// Real part of the inverse transform.
+    pub fn inverse(&self) -> Field {
+        let values: Vec<f32> = transform_2d(&self.data, self.width, self.height(), true)
+            .into_iter()
+            .map(|c| c.re as f32)
+            .collect();
+
+        Field {
+            flattened_field: values.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+}
+
+/// Power averaged over rings of equal radial frequency.
+#[derive(Debug, Clone)]
+pub struct RadialSpectrum {
+    /// mean radial frequency of each ring, in cycles per cell
+    pub frequencies: Vec<f32>,
+    pub power: Vec<f32>,
+}
+
+impl RadialSpectrum {
+    /// Exponent β of the power law P ∝ f^β fitted in log-log space over the rings between
+    /// the two frequencies. Natural terrain usually lands around -2 to -3.
+    pub fn slope(
+        &self,
+        min_frequency: f32,
+        max_frequency: f32,
+    ) -> Result<f32, Box<dyn std::error::Error>> {
+        let points: Vec<(f64, f64)> = self
+            .frequencies
+            .iter()
+            .zip(self.power.iter())
+            .filter(|&(&f, &p)| f >= min_frequency && f <= max_frequency && p > 0.0)
+            .map(|(&f, &p)| ((f as f64).log10(), (p as f64).log10()))
+            .collect();
+        if points.len() < 2 {
+            return Err("Not enough spectrum rings in the frequency range to fit a slope".into());
+        }
+
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let (covariance, variance) = points.iter().fold((0.0, 0.0), |(c, v), &(x, y)| {
+            (
+                c + (x - mean_x) * (y - mean_y),
+                v + (x - mean_x) * (x - mean_x),
+            )
+        });
+        Ok((covariance / variance) as f32)
+    }
+}
+
+impl Field {
+    /// Complex 2D discrete Fourier transform, any size.
+    pub fn fft(&self) -> Spectrum {
+        let data: Vec<Complex> = self
+            .flattened_field
+            .par_iter()
+            .map(|&v| Complex::new(v as f64, 0.0))
+            .collect();
+
+        Spectrum {
+            data: transform_2d(&data, self.width, self.height(), false).into_boxed_slice(),
+            width: self.width,
+        }
+    }
+
+    /// Mean-removed and windowed spectrum, scaled so the powers add up to the windowed
+    /// variance.
+    fn windowed_power(&self, window: Window) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        window.check()?;
+        let width = self.width;
+        let stats = self.stats();
+        if stats.nan_count > 0 || stats.count != self.flattened_field.len() {
+            return Err("Spectral analysis needs a field without NaN or infinite values".into());
+        }
+
+        let wx = window.weights(width);
+        let wy = window.weights(self.height());
+        let mean = stats.mean as f64;
+        let data: Vec<Complex> = self
+            .flattened_field
+            .par_iter()
+            .enumerate()
+            .map(|(i, &v)| Complex::new((v as f64 - mean) * wx[i % width] * wy[i / width], 0.0))
+            .collect();
+        let energy: f64 =
+            wy.iter().map(|a| a * a).sum::<f64>() * wx.iter().map(|a| a * a).sum::<f64>();
+        let norm = 1.0 / (energy * data.len() as f64);
+
+        Ok(transform_2d(&data, width, self.height(), false)
+            .into_par_iter()
+            .map(|c| c.norm_sqr() * norm)
+            .collect())
+    }
+
+    /// Periodogram with the zero frequency moved to the centre, ready for viewing with
+    /// `Normalization::Log`.
+    pub fn power_spectrum(&self, window: Window) -> Result<Self, Box<dyn std::error::Error>> {
+        let width = self.width;
+        let height = self.height();
+        let power = self.windowed_power(window)?;
+
+        Self::from_fn(width, height, |x, y| {
+            let u = (x + width - width / 2) % width;
+            let v = (y + height - height / 2) % height;
+            power[v * width + u] as f32
+        })
+    }
+
+    /// Periodogram averaged over rings one frequency step wide, from the lowest non-zero
+    /// frequency up to Nyquist.
+    pub fn radial_spectrum(
+        &self,
+        window: Window,
+    ) -> Result<RadialSpectrum, Box<dyn std::error::Error>> {
+        let width = self.width;
+        let height = self.height();
+        let power = self.windowed_power(window)?;
+
+        let size = width.max(height);
+        let rings = size / 2;
+        let mut sums = vec![(0.0_f64, 0.0_f64, 0_usize); rings + 1];
+        for (i, &p) in power.iter().enumerate() {
+            let (fx, fy) = (frequency(i % width, width), frequency(i / width, height));
+            let f = fx.hypot(fy);
+            let ring = (f * size as f64).round() as usize;
+            if ring == 0 || ring > rings {
+                continue;
+            }
+            sums[ring].0 += f;
+            sums[ring].1 += p;
+            sums[ring].2 += 1;
+        }
+
+        let (frequencies, power) = sums
+            .into_iter()
+            .filter(|s| s.2 > 0)
+            .map(|(f, p, n)| ((f / n as f64) as f32, (p / n as f64) as f32))
+            .unzip();
+        Ok(RadialSpectrum { frequencies, power })
+    }
+
+    /// Power-law exponent of the radial spectrum over all frequencies, see
+    /// `RadialSpectrum::slope`.
+    pub fn spectral_slope(&self, window: Window) -> Result<f32, Box<dyn std::error::Error>> {
+        self.radial_spectrum(window)?.slope(0.0, 0.5)
+    }
+
+    /// Keeps one frequency band of the field. Low-pass gives the broad shape, high-pass the
+    /// fine detail, and the two add back up to the original.
+    pub fn frequency_filter(
+        &self,
+        filter: FrequencyFilter,
+        options: &FilterOptions,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let cutoffs = match filter {
+            FrequencyFilter::LowPass(c) | FrequencyFilter::HighPass(c) => vec![c],
+            FrequencyFilter::BandPass(low, high) => {
+                if low >= high {
+                    return Err(format!("Invalid pass band: {}..{}", low, high).into());
+                }
+                vec![low, high]
+            }
+        };
+        if cutoffs.iter().any(|c| c.is_nan() || *c < 0.0) {
+            return Err(format!("Invalid cutoff frequency in {:?}", filter).into());
+        }
+        if options.rolloff.is_nan() || options.rolloff < 0.0 {
+            return Err(format!("Invalid filter rolloff: {}", options.rolloff).into());
+        }
+        if options.padding.is_nan() || !(0.0..=1.0).contains(&options.padding) {
+            return Err(format!("Invalid filter padding: {}", options.padding).into());
+        }
+        let stats = self.stats();
+        if stats.nan_count > 0 || stats.count != self.flattened_field.len() {
+            return Err("Filtering needs a field without NaN or infinite values".into());
+        }
+
+        let width = self.width;
+        let height = self.height();
+        let mean = stats.mean as f64;
+
+        // mirror the edges outwards and fade them to the mean
+        let pad_x = ((width as f32 * options.padding) as usize).min(width - 1);
+        let pad_y = ((height as f32 * options.padding) as usize).min(height - 1);
+        let (padded_width, padded_height) = (width + 2 * pad_x, height + 2 * pad_y);
+        let reflect = |i: isize, n: usize| -> (usize, f64) {
+            let n = n as isize;
+            let (j, outside) = if i < 0 {
+                (-i, -i)
+            } else if i >= n {
+                (2 * (n - 1) - i, i - (n - 1))
+            } else {
+                (i, 0)
+            };
+            (j as usize, outside as f64)
+        };
+        let fade = |outside: f64, pad: usize| {
+            if outside == 0.0 {
+                1.0
+            } else {
+                0.5 + 0.5 * (PI * outside / (pad + 1) as f64).cos()
+            }
+        };
+        let data: Vec<Complex> = (0..padded_width * padded_height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, dx) = reflect((i % padded_width) as isize - pad_x as isize, width);
+                let (y, dy) = reflect((i / padded_width) as isize - pad_y as isize, height);
+                let taper = fade(dx, pad_x) * fade(dy, pad_y);
+                Complex::new((self.get(x, y) as f64 - mean) * taper, 0.0)
+            })
+            .collect();
+
+        // raised-cosine step from 1 below the cutoff to 0 above it
+        let rolloff = options.rolloff as f64;
+        let low_pass = |f: f64, cutoff: f32| {
+            let cutoff = cutoff as f64;
+            if f <= cutoff - rolloff / 2.0 {
+                1.0
+            } else if f >= cutoff + rolloff / 2.0 {
+                0.0
+            } else {
+                0.5 + 0.5 * (PI * (f - cutoff + rolloff / 2.0) / rolloff).cos()
+            }
+        };
+        let response = |f: f64| match filter {
+            FrequencyFilter::LowPass(c) => low_pass(f, c),
+            FrequencyFilter::HighPass(c) => 1.0 - low_pass(f, c),
+            FrequencyFilter::BandPass(low, high) => low_pass(f, high) * (1.0 - low_pass(f, low)),
+        };
+
+        let mut spectrum = transform_2d(&data, padded_width, padded_height, false);
+        spectrum.par_iter_mut().enumerate().for_each(|(i, c)| {
+            let fx = frequency(i % padded_width, padded_width);
+            let fy = frequency(i / padded_width, padded_height);
+            *c = c.scale(response(fx.hypot(fy)));
+        });
+        let filtered = transform_2d(&spectrum, padded_width, padded_height, true);
+
+        // the mean is the zero frequency, so it belongs to the low band
+        let offset = mean * response(0.0);
+        Self::from_fn(width, height, |x, y| {
+            (filtered[(y + pad_y) * padded_width + x + pad_x].re + offset) as f32
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bumps(width: usize, height: usize) -> Field {
+        Field::from_fn(width, height, |x, y| {
+            let (x, y) = (x as f32, y as f32);
+            (x * 0.9).sin() * (y * 0.4).cos() + (x * 0.05 + y * 0.13).sin() * 0.5
+        })
+        .unwrap()
+    }
+
+    fn max_difference(a: &Field, b: &Field) -> f32 {
+        a.flattened_field
+            .iter()
+            .zip(b.flattened_field.iter())
+            .fold(0.0, |m, (a, b)| m.max((a - b).abs()))
+    }
+
+    #[test]
+    fn odd_size_round_trip() {
+        // neither side is a power of two, so both go through Bluestein
+        let field = bumps(15, 9);
+        let restored = field.fft().inverse();
+
+        assert_eq!((restored.width, restored.height()), (15, 9));
+        assert!(max_difference(&field, &restored) < 1e-6);
+    }
+
+    #[test]
+    fn low_and_high_pass_add_up() {
+        let field = bumps(37, 24);
+        let options = FilterOptions::default();
+        let low = field
+            .frequency_filter(FrequencyFilter::LowPass(0.1), &options)
+            .unwrap();
+        let high = field
+            .frequency_filter(FrequencyFilter::HighPass(0.1), &options)
+            .unwrap();
+
+        let sum = low.zip_with(&high, |a, b| a + b).unwrap();
+        assert!(max_difference(&field, &sum) < 1e-6);
+        assert!(max_difference(&field, &low) > 0.1);
+    }
+}
@@ -1,12 +1,14 @@
 use std::fs::File;
-use std::io::{Cursor, Read};
+use std::io::{BufWriter, Cursor, Read, Write};
 use std::path::Path;
-use std::u16;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use hashbrown::HashMap;
 use image::{DynamicImage, ImageBuffer, ImageError, Luma, RgbaImage};
 
+use crate::field::seamless::Boundary;
+use crate::field::stats::Normalization;
+use crate::field::view::Raster;
 use crate::hex::hex::Hex;
 use crate::hex::layout::Layout;
 use crate::hex::point::Point;
@@ -29,11 +31,18 @@ impl Bin {
     }
 }
 
+#[derive(Clone)]
 pub struct Field {
     pub flattened_field: Box<[f32]>,
     pub width: usize,
 }
 
+impl Default for Field {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Field {
     pub fn new() -> Self {
         Self {
@@ -42,13 +51,22 @@ impl Field {
         }
     }
 
-    pub fn from_r32(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut file = File::open(path)?;
+    /// bilinear read at a fractional cell position, clamped like `sample`
+    pub fn sample_bilinear(&self, x: f32, y: f32) -> f32 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = self.sample(x0, y0) * (1.0 - tx) + self.sample(x0 + 1, y0) * tx;
+        let bottom = self.sample(x0, y0 + 1) * (1.0 - tx) + self.sample(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
 
-        let file_size = file.metadata()?.len();
+    pub fn from_r32(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file_size = std::fs::metadata(path)?.len();
         if file_size != (ARRAY_LEN * std::mem::size_of::<f32>()) as u64 {
-            let extra_bytes = file_size - (ARRAY_LEN * 4) as u64;
-            println!("Warning: Ignoring {} extra bytes in the file.", extra_bytes);
             return Err(format!(
                 "Unexpected file size: expected {}, got {}",
                 ARRAY_LEN * 4,
@@ -57,11 +75,32 @@ impl Field {
             .into());
         }
 
-        let mut buffer = vec![0_u8; ARRAY_LEN * 4];
+        Self::from_r32_with_width(path, IMG_WIDTH)
+    }
+
+    /// reads a headerless little-endian f32 raster, the height is taken from the file size
+    pub fn from_r32_with_width(
+        path: &Path,
+        width: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut file = File::open(path)?;
+
+        let file_size = file.metadata()?.len();
+        let row_bytes = (width * std::mem::size_of::<f32>()) as u64;
+        if width == 0 || file_size == 0 || file_size % row_bytes != 0 {
+            return Err(format!(
+                "Unexpected file size: {} is not a whole number of {} byte rows",
+                file_size, row_bytes
+            )
+            .into());
+        }
+        let len = (file_size / 4) as usize;
+
+        let mut buffer = vec![0_u8; len * 4];
         file.read_exact(&mut buffer)?;
 
         let mut cursor = Cursor::new(buffer);
-        let mut normalized_f32 = vec![0_f32; ARRAY_LEN].into_boxed_slice();
+        let mut normalized_f32 = vec![0_f32; len].into_boxed_slice();
 
         for val in normalized_f32.iter_mut() {
             if cursor.position() >= cursor.get_ref().len() as u64 {
@@ -72,14 +111,23 @@ impl Field {
 
         Ok(Self {
             flattened_field: normalized_f32,
-            width: IMG_WIDTH,
+            width,
         })
     }
 
+    pub fn write_r32(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for &value in self.flattened_field.iter() {
+            out.write_f32::<LittleEndian>(value)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
     pub fn to_resized_rgba_image(&self, max_size: u32) -> Vec<u8> {
         //let original_size = (self.width as u32, self.width as u32);
 
-        let mut image = RgbaImage::new(self.width as u32, self.width as u32);
+        let mut image = RgbaImage::new(self.width as u32, self.height() as u32);
         self.flattened_field
             .iter()
             .enumerate()
@@ -98,8 +146,6 @@ impl Field {
     }
 
     pub fn write_png_u16(&self, path: &Path) -> Result<(), ImageError> {
-        assert_eq!(ARRAY_LEN, IMG_WIDTH * IMG_WIDTH, "Image is not square!");
-
         let (min, max) = self
             .flattened_field
             .iter()
@@ -107,14 +153,14 @@ impl Field {
                 (min.min(value), max.max(value))
             });
 
-        let range = if max - min > std::f32::EPSILON {
+        let range = if max - min > f32::EPSILON {
             max - min
         } else {
             1.0 // Avoid division by zero; treat as uniform field
         };
 
-        let img = ImageBuffer::from_fn(IMG_WIDTH as u32, IMG_WIDTH as u32, |x, y| {
-            let value = self.flattened_field[(y as usize * IMG_WIDTH) + x as usize];
+        let img = ImageBuffer::from_fn(self.width as u32, self.height() as u32, |x, y| {
+            let value = self.flattened_field[(y as usize * self.width) + x as usize];
             let normalized_value = (value - min) / range;
             let u16_value = (normalized_value * u16::MAX as f32) as u16;
             Luma::<u16>([u16_value])
@@ -123,14 +169,15 @@ impl Field {
         img.save(path)
     }
 
-    pub fn hex_aggregate(&self, layout: Layout) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Sum and pixel count of the field per hex of `layout`, hexes that no pixel falls in
+    /// stay empty.
+    pub(crate) fn hex_bins(&self, layout: &Layout) -> HashMap<Hex, Bin> {
         let mut bin: HashMap<Hex, Bin> = HashMap::new();
-        let mut hex_field: Box<[f32; ARRAY_LEN]> = Box::new([0.0; ARRAY_LEN]);
 
-        const SQRT_3: f32 = 1.73205080756888;
+        const SQRT_3: f32 = 1.732_050_8;
         let size = layout.size.x as f32;
 
-        let height = self.width as f32 / (size * SQRT_3);
+        let height = self.height() as f32 / (size * SQRT_3);
         let width = self.width as f32 / (size * 3_f32 / 2_f32);
 
         let left = -1;
@@ -150,36 +197,37 @@ impl Field {
             .enumerate()
             .for_each(|(i, value)| {
                 let hex = Hex::from(Hex::from_point(
-                    &layout,
+                    layout,
                     &Point {
-                        x: (i % IMG_WIDTH) as f64,
-                        y: (i / IMG_WIDTH) as f64,
+                        x: (i % self.width) as f64,
+                        y: (i / self.width) as f64,
                     },
                 ));
 
-                match bin.get_mut(&hex) {
-                    Some(basket) => {
-                        basket.agr_value += value;
-                        basket.pixel_count += 1;
-                    }
-                    None => (),
+                if let Some(basket) = bin.get_mut(&hex) {
+                    basket.agr_value += value;
+                    basket.pixel_count += 1;
                 }
             });
 
+        bin
+    }
+
+    pub fn hex_aggregate(&self, layout: Layout) -> Result<Self, Box<dyn std::error::Error>> {
+        let bin = self.hex_bins(&layout);
+        let mut hex_field = vec![0_f32; self.flattened_field.len()].into_boxed_slice();
+
         for i in 0..hex_field.len() {
             let hex = Hex::from(Hex::from_point(
                 &layout,
                 &Point {
-                    x: (i % IMG_WIDTH) as f64,
-                    y: (i / IMG_WIDTH) as f64,
+                    x: (i % self.width) as f64,
+                    y: (i / self.width) as f64,
                 },
             ));
 
-            match bin.get(&hex) {
-                Some(value) => {
-                    hex_field[i] = value.agr_value / value.pixel_count as f32;
-                }
-                None => (),
+            if let Some(value) = bin.get(&hex) {
+                hex_field[i] = value.agr_value / value.pixel_count as f32;
             }
         }
 
@@ -192,11 +240,18 @@ impl Field {
     /// # Args desc bc i'll forget lol
     /// * `dx` - horizontal shift (positive is right, negative is left)
     /// * `dy` - vertical shift (positive is down, negative is up)
-    fn shift(field: &[f32], width: usize, dx: isize, dy: isize) -> Vec<f32> {
-        let height = field.len() / width;
-        let mut shifted = vec![0.0; field.len()];
-
-        for i in 0..field.len() {
+    /// * `boundary` - `Wrap` reads across the opposite edge, `Clamp` keeps the cell's own value
+    fn shift<R: Raster + ?Sized>(
+        raster: &R,
+        dx: isize,
+        dy: isize,
+        boundary: Boundary,
+    ) -> Vec<f32> {
+        let width = raster.width();
+        let height = raster.height();
+        let mut shifted = vec![0.0; width * height];
+
+        for (i, value) in shifted.iter_mut().enumerate() {
             let row = i / width;
             let col = i % width;
 
@@ -205,264 +260,230 @@ impl Field {
 
             if new_row >= 0 && new_row < height as isize && new_col >= 0 && new_col < width as isize
             {
-                let new_idx = (new_row as usize) * width + (new_col as usize);
-                shifted[i] = field[new_idx];
+                *value = raster.get(new_col as usize, new_row as usize);
+            } else if boundary == Boundary::Wrap {
+                *value = raster.get(
+                    new_col.rem_euclid(width as isize) as usize,
+                    new_row.rem_euclid(height as isize) as usize,
+                );
             } else {
                 // retain the original value at the boundary
-                shifted[i] = field[i];
+                *value = raster.get(col, row);
             }
         }
 
         shifted
     }
 
-    pub fn sobel(&self) -> Result<Self, Box<dyn std::error::Error>> {
-        let top_left =      Self::shift(&self.flattened_field, IMG_WIDTH, -1, -1);
-        let top =           Self::shift(&self.flattened_field, IMG_WIDTH, 0, -1);
-        let top_right =     Self::shift(&self.flattened_field, IMG_WIDTH, 1, -1);
-
-        let left =          Self::shift(&self.flattened_field, IMG_WIDTH, -1, 0);
-        let right =         Self::shift(&self.flattened_field, IMG_WIDTH, 1, 0);
-
-        let bottom_left =   Self::shift(&self.flattened_field, IMG_WIDTH, -1, 1);
-        let bottom =        Self::shift(&self.flattened_field, IMG_WIDTH, 0, 1);
-        let bottom_right =  Self::shift(&self.flattened_field, IMG_WIDTH, 1, 1);
-
-        let mut gradient_x = vec![0.0; ARRAY_LEN];
-        let mut gradient_y = vec![0.0; ARRAY_LEN];
-
-        for i in 0..ARRAY_LEN {
-            gradient_x[i] = (top_right[i] + 2.0 * right[i] + bottom_right[i])
-                - (top_left[i] + 2.0 * left[i] + bottom_left[i]);
-
-            gradient_y[i] = (top_left[i] + 2.0 * top[i] + top_right[i])
-                - (bottom_left[i] + 2.0 * bottom[i] + bottom_right[i]);
-        }
-
-        // Combine gradients to compute magnitude
-        let mut result = vec![0.0; ARRAY_LEN];
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
-
-        for i in 0..ARRAY_LEN {
-            result[i] = (gradient_x[i].powi(2) + gradient_y[i].powi(2)).sqrt();
-            min = min.min(result[i]);
-            max = max.max(result[i]);
-        }
+    fn compute_eigenvalues(hessian: [[f32; 2]; 2]) -> (f32, f32) {
+        let trace = hessian[0][0] + hessian[1][1];
+        let determinant = hessian[0][0] * hessian[1][1] - hessian[0][1] * hessian[1][0];
+        let discriminant = (trace.powi(2) - 4.0 * determinant).sqrt();
 
-        for v in result.iter_mut() {
-            if max - min > std::f32::EPSILON {
-                *v = (*v - min) / (max - min);
-            } else {
-                *v = 0.0;
-            }
-        }
+        let lambda1 = (trace + discriminant) / 2.0;
+        let lambda2 = (trace - discriminant) / 2.0;
 
-        Ok(Self {
-            flattened_field: result.into_boxed_slice(),
-            width: IMG_WIDTH,
-        })
+        (lambda1, lambda2)
     }
 
-    pub fn prewitt(&self) -> Result<Self, Box<dyn std::error::Error>> {
-        let top_left =      Self::shift(&self.flattened_field, IMG_WIDTH, -1, -1);
-        let top =           Self::shift(&self.flattened_field, IMG_WIDTH, 0, -1);
-        let top_right =     Self::shift(&self.flattened_field, IMG_WIDTH, 1, -1);
-
-        let left =          Self::shift(&self.flattened_field, IMG_WIDTH, -1, 0);
-        let right =         Self::shift(&self.flattened_field, IMG_WIDTH, 1, 0);
-
-        let bottom_left =   Self::shift(&self.flattened_field, IMG_WIDTH, -1, 1);
-        let bottom =        Self::shift(&self.flattened_field, IMG_WIDTH, 0, 1);
-        let bottom_right =  Self::shift(&self.flattened_field, IMG_WIDTH, 1, 1);
-
-        let mut gradient_x = vec![0.0; ARRAY_LEN];
-        let mut gradient_y = vec![0.0; ARRAY_LEN];
-
-        for i in 0..ARRAY_LEN {
-            gradient_x[i] = (top_right[i] + right[i] + bottom_right[i])
-                - (top_left[i] + left[i] + bottom_left[i]);
-
-            gradient_y[i] = (top_left[i] + top[i] + top_right[i])
-                - (bottom_left[i] + bottom[i] + bottom_right[i]);
+    /// Min-max rescale into `new_min..=new_max`, see `normalize_with` for other modes.
+    pub fn normalize(
+        &self,
+        new_min: f32,
+        new_max: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if self.flattened_field.is_empty() {
+            return Err("Normalization failed: The field is empty.".into());
         }
 
-        let mut result = vec![0.0; ARRAY_LEN];
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
-
-        for i in 0..ARRAY_LEN {
-            result[i] = (gradient_x[i].powi(2) + gradient_y[i].powi(2)).sqrt();
-            min = min.min(result[i]);
-            max = max.max(result[i]);
-        }
+        let (min, max) = self.min_max();
 
-        for v in result.iter_mut() {
-            if max - min > std::f32::EPSILON {
-                *v = (*v - min) / (max - min);
-            } else {
-                *v = 0.0;
-            }
+        if (max - min).abs() < f32::EPSILON {
+            return Err("Normalization failed: All values in the field are identical.".into());
         }
 
-        Ok(Self {
-            flattened_field: result.into_boxed_slice(),
-            width: IMG_WIDTH,
-        })
+        self.normalize_with(Normalization::MinMax, new_min, new_max)
     }
+}
 
-    pub fn steepness(&self) -> Result<Self, Box<dyn std::error::Error>> {
-        let shifted_right =     Self::shift(&self.flattened_field, IMG_WIDTH, 1, 0);
-        let shifted_down =      Self::shift(&self.flattened_field, IMG_WIDTH, 0, 1);
+/// See `Raster::sobel`.
+pub(crate) fn sobel<R: Raster + ?Sized>(raster: &R) -> Result<Field, Box<dyn std::error::Error>> {
+    let len = raster.width() * raster.height();
+    let boundary = raster.boundary();
+    let top_left =      Field::shift(raster, -1, -1, boundary);
+    let top =           Field::shift(raster, 0, -1, boundary);
+    let top_right =     Field::shift(raster, 1, -1, boundary);
 
-        let mut result = vec![0.0; ARRAY_LEN];
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
+    let left =          Field::shift(raster, -1, 0, boundary);
+    let right =         Field::shift(raster, 1, 0, boundary);
 
-        for i in 0..ARRAY_LEN {
-            let dx = shifted_right[i] - self.flattened_field[i];
-            let dy = shifted_down[i] - self.flattened_field[i];
-            result[i] = (dx * dx + dy * dy).sqrt();
+    let bottom_left =   Field::shift(raster, -1, 1, boundary);
+    let bottom =        Field::shift(raster, 0, 1, boundary);
+    let bottom_right =  Field::shift(raster, 1, 1, boundary);
 
-            min = min.min(result[i]);
-            max = max.max(result[i]);
-        }
+    let mut gradient_x = vec![0.0; len];
+    let mut gradient_y = vec![0.0; len];
 
-        for v in result.iter_mut() {
-            if max - min > std::f32::EPSILON {
-                *v = (*v - min) / (max - min);
-            } else {
-                *v = 0.0;
-            }
-        }
+    for i in 0..len {
+        gradient_x[i] = (top_right[i] + 2.0 * right[i] + bottom_right[i])
+            - (top_left[i] + 2.0 * left[i] + bottom_left[i]);
 
-        Ok(Self {
-            flattened_field: result.into_boxed_slice(),
-            width: IMG_WIDTH,
-        })
+        gradient_y[i] = (top_left[i] + 2.0 * top[i] + top_right[i])
+            - (bottom_left[i] + 2.0 * bottom[i] + bottom_right[i]);
     }
 
-    fn compute_eigenvalues(hessian: [[f32; 2]; 2]) -> (f32, f32) {
-        let trace = hessian[0][0] + hessian[1][1];
-        let determinant = hessian[0][0] * hessian[1][1] - hessian[0][1] * hessian[1][0];
-        let discriminant = (trace.powi(2) - 4.0 * determinant).sqrt();
+    // Combine gradients to compute magnitude
+    let mut result = vec![0.0; len];
+    for i in 0..len {
+        result[i] = (gradient_x[i].powi(2) + gradient_y[i].powi(2)).sqrt();
+    }
 
-        let lambda1 = (trace + discriminant) / 2.0;
-        let lambda2 = (trace - discriminant) / 2.0;
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
 
-        (lambda1, lambda2)
-    }
+/// See `Raster::prewitt`.
+pub(crate) fn prewitt<R: Raster + ?Sized>(raster: &R) -> Result<Field, Box<dyn std::error::Error>> {
+    let len = raster.width() * raster.height();
+    let boundary = raster.boundary();
+    let top_left =      Field::shift(raster, -1, -1, boundary);
+    let top =           Field::shift(raster, 0, -1, boundary);
+    let top_right =     Field::shift(raster, 1, -1, boundary);
 
-    pub fn normalize(
-        &self,
-        new_min: f32,
-        new_max: f32,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
+    let left =          Field::shift(raster, -1, 0, boundary);
+    let right =         Field::shift(raster, 1, 0, boundary);
 
-        for &value in self.flattened_field.iter() {
-            min = min.min(value);
-            max = max.max(value);
-        }
+    let bottom_left =   Field::shift(raster, -1, 1, boundary);
+    let bottom =        Field::shift(raster, 0, 1, boundary);
+    let bottom_right =  Field::shift(raster, 1, 1, boundary);
 
-        if max - min < std::f32::EPSILON {
-            return Err("Normalization failed: All values in the field are identical.".into());
-        }
+    let mut gradient_x = vec![0.0; len];
+    let mut gradient_y = vec![0.0; len];
 
-        let range = max - min;
-        let new_range = new_max - new_min;
+    for i in 0..len {
+        gradient_x[i] = (top_right[i] + right[i] + bottom_right[i])
+            - (top_left[i] + left[i] + bottom_left[i]);
 
-        let normalized_field: Vec<f32> = self
-            .flattened_field
-            .iter()
-            .map(|&value| ((value - min) / range) * new_range + new_min)
-            .collect();
+        gradient_y[i] = (top_left[i] + top[i] + top_right[i])
+            - (bottom_left[i] + bottom[i] + bottom_right[i]);
+    }
 
-        Ok(Self {
-            flattened_field: normalized_field.into_boxed_slice(),
-            width: self.width,
-        })
+    let mut result = vec![0.0; len];
+    for i in 0..len {
+        result[i] = (gradient_x[i].powi(2) + gradient_y[i].powi(2)).sqrt();
     }
 
-    pub fn structural_lines(&self) -> Result<(Self, Self, Self, Self), Box<dyn std::error::Error>> {
-        let gradient_x = Self::shift(&self.flattened_field, self.width, 1, 0)
-            .iter()
-            .zip(self.flattened_field.iter())
-            .map(|(a, b)| a - b)
-            .collect::<Vec<f32>>();
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
 
-        let gradient_y = Self::shift(&self.flattened_field, self.width, 0, 1)
-            .iter()
-            .zip(self.flattened_field.iter())
-            .map(|(a, b)| a - b)
-            .collect::<Vec<f32>>();
+/// See `Raster::steepness`.
+pub(crate) fn steepness<R: Raster + ?Sized>(
+    raster: &R,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    let len = raster.width() * raster.height();
+    let boundary = raster.boundary();
+    let shifted_right =     Field::shift(raster, 1, 0, boundary);
+    let shifted_down =      Field::shift(raster, 0, 1, boundary);
+
+    let mut result = vec![0.0; len];
+
+    for (i, value) in raster.values().enumerate() {
+        let dx = shifted_right[i] - value;
+        let dy = shifted_down[i] - value;
+        result[i] = (dx * dx + dy * dy).sqrt();
+    }
 
-        let dxx = Self::shift(&gradient_x, self.width, 1, 0)
-            .iter()
-            .zip(gradient_x.iter())
-            .map(|(a, b)| a - b)
-            .collect::<Vec<f32>>();
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
 
-        let dyy = Self::shift(&gradient_y, self.width, 0, 1)
+/// See `Raster::structural_lines`.
+pub(crate) fn structural_lines<R: Raster + ?Sized>(
+    raster: &R,
+) -> Result<(Field, Field, Field, Field), Box<dyn std::error::Error>> {
+    let len = raster.width() * raster.height();
+    let boundary = raster.boundary();
+    let gradient_x = Field {
+        flattened_field: Field::shift(raster, 1, 0, boundary)
             .iter()
-            .zip(gradient_y.iter())
+            .zip(raster.values())
             .map(|(a, b)| a - b)
-            .collect::<Vec<f32>>();
+            .collect(),
+        width: raster.width(),
+    };
 
-        let dxy = Self::shift(&gradient_x, self.width, 0, 1)
+    let gradient_y = Field {
+        flattened_field: Field::shift(raster, 0, 1, boundary)
             .iter()
-            .zip(gradient_y.iter())
+            .zip(raster.values())
             .map(|(a, b)| a - b)
-            .collect::<Vec<f32>>();
-
-        let mut crests =            vec![0.0; self.flattened_field.len()];
-        let mut thalwegs =          vec![0.0; self.flattened_field.len()];
-        let mut convex_lines =      vec![0.0; self.flattened_field.len()];
-        let mut concave_lines =     vec![0.0; self.flattened_field.len()];
-
-        for i in 0..self.flattened_field.len() {
-            let hessian = [[dxx[i], dxy[i]], [dxy[i], dyy[i]]];
-            let (lambda1, lambda2) = Self::compute_eigenvalues(hessian);
-
-            if lambda1 > 0.0 {
-                crests[i] = lambda1;
-            } else if lambda1 < 0.0 {
-                thalwegs[i] = lambda1;
-            }
-
-            if lambda2 > 0.0 {
-                convex_lines[i] = lambda2;
-            } else if lambda2 < 0.0 {
-                concave_lines[i] = lambda2;
-            }
+            .collect(),
+        width: raster.width(),
+    };
+
+    let dxx = Field::shift(&gradient_x, 1, 0, boundary)
+        .iter()
+        .zip(gradient_x.flattened_field.iter())
+        .map(|(a, b)| a - b)
+        .collect::<Vec<f32>>();
+
+    let dyy = Field::shift(&gradient_y, 0, 1, boundary)
+        .iter()
+        .zip(gradient_y.flattened_field.iter())
+        .map(|(a, b)| a - b)
+        .collect::<Vec<f32>>();
+
+    let dxy = Field::shift(&gradient_x, 0, 1, boundary)
+        .iter()
+        .zip(gradient_y.flattened_field.iter())
+        .map(|(a, b)| a - b)
+        .collect::<Vec<f32>>();
+
+    let mut crests =            vec![0.0; len];
+    let mut thalwegs =          vec![0.0; len];
+    let mut convex_lines =      vec![0.0; len];
+    let mut concave_lines =     vec![0.0; len];
+
+    for i in 0..len {
+        let hessian = [[dxx[i], dxy[i]], [dxy[i], dyy[i]]];
+        let (lambda1, lambda2) = Field::compute_eigenvalues(hessian);
+
+        if lambda1 > 0.0 {
+            crests[i] = lambda1;
+        } else if lambda1 < 0.0 {
+            thalwegs[i] = lambda1;
         }
 
-        Ok((
-            Self {
-                flattened_field: crests.into_boxed_slice(),
-                width: self.width,
-            }
-            .normalize(0.0, 1.0)
-            .expect("Failed to normalize crests field"),
-            Self {
-                flattened_field: thalwegs.into_boxed_slice(),
-                width: self.width,
-            }
-            .normalize(0.0, 1.0)
-            .expect("Failed to normalize thalwegs field"),
-            Self {
-                flattened_field: convex_lines.into_boxed_slice(),
-                width: self.width,
-            }
-            .normalize(0.0, 1.0)
-            .expect("Failed to normalize convex_lines field"),
-            Self {
-                flattened_field: concave_lines.into_boxed_slice(),
-                width: self.width,
-            }
-            .normalize(0.0, 1.0)
-            .expect("Failed to normalize concave_lines field"),
-        ))
+        if lambda2 > 0.0 {
+            convex_lines[i] = lambda2;
+        } else if lambda2 < 0.0 {
+            concave_lines[i] = lambda2;
+        }
     }
+
+    Ok((
+        Field {
+            flattened_field: crests.into_boxed_slice(),
+            width: raster.width(),
+        },
+        Field {
+            flattened_field: thalwegs.into_boxed_slice(),
+            width: raster.width(),
+        },
+        Field {
+            flattened_field: convex_lines.into_boxed_slice(),
+            width: raster.width(),
+        },
+        Field {
+            flattened_field: concave_lines.into_boxed_slice(),
+            width: raster.width(),
+        },
+    ))
 }
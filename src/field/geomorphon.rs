@@ -0,0 +1,197 @@
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::hydrology::D8_OFFSETS;
+use crate::field::view::Raster;
+
+/// The ten geomorphon landforms, numbered as in GRASS r.geomorphon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Landform {
+    Flat = 1,
+    Peak = 2,
+    Ridge = 3,
+    Shoulder = 4,
+    Spur = 5,
+    Slope = 6,
+    Hollow = 7,
+    Footslope = 8,
+    Valley = 9,
+    Pit = 10,
+}
+
+impl Landform {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Lookup table from Jasiewicz & Stepinski (2013), indexed by the number of
+    /// lower (-) and higher (+) directions. Entries past lower + higher = 8 are unreachable.
+    fn classify(lower: usize, higher: usize) -> Self {
+        use Landform::*;
+
+        #[rustfmt::skip]
+        const FORMS: [[Landform; 9]; 9] = [
+            [Flat, Flat, Flat, Footslope, Footslope, Valley, Valley, Valley, Pit],
+            [Flat, Flat, Footslope, Footslope, Footslope, Valley, Valley, Valley, Valley],
+            [Flat, Shoulder, Slope, Slope, Hollow, Hollow, Valley, Valley, Valley],
+            [Shoulder, Shoulder, Slope, Slope, Slope, Hollow, Hollow, Hollow, Hollow],
+            [Shoulder, Shoulder, Spur, Slope, Slope, Slope, Slope, Slope, Slope],
+            [Ridge, Ridge, Spur, Spur, Spur, Spur, Spur, Spur, Spur],
+            [Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge],
+            [Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge, Ridge],
+            [Peak, Peak, Peak, Peak, Peak, Peak, Peak, Peak, Peak],
+        ];
+
+        FORMS[lower][higher]
+    }
+}
+
+pub struct LandformMap {
+    pub landforms: Box<[Landform]>,
+    pub width: usize,
+}
+
+impl LandformMap {
+    pub fn height(&self) -> usize {
+        self.landforms.len() / self.width
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Landform {
+        self.landforms[y * self.width + x]
+    }
+
+    pub fn count(&self, landform: Landform) -> usize {
+        self.landforms.iter().filter(|&&l| l == landform).count()
+    }
+
+    /// landform codes as a field, e.g. for `write_png_u16`
+    pub fn to_field(&self) -> Field {
+        let codes: Vec<f32> = self.landforms.iter().map(|l| l.code() as f32).collect();
+
+        Field {
+            flattened_field: codes.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+}
+
+impl Field {
+    /// Geomorphon landform classification (Jasiewicz & Stepinski 2013).
+    ///
+    /// Each of the 8 compass directions is scanned out to `lookup_distance` cells. A direction
+    /// counts as higher (+) or lower (-) when the difference between its nadir and zenith
+    /// angles exceeds `flatness_threshold` (radians), and the counts of both pick the landform.
+    pub fn geomorphons(
+        &self,
+        lookup_distance: usize,
+        flatness_threshold: f32,
+        cell_size: f32,
+    ) -> Result<LandformMap, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        if lookup_distance == 0 {
+            return Err("Geomorphon lookup distance must be at least one cell".into());
+        }
+        if flatness_threshold.is_nan() || flatness_threshold < 0.0 {
+            return Err(format!("Invalid flatness threshold: {}", flatness_threshold).into());
+        }
+
+        let width = self.width as isize;
+        let height = self.height() as isize;
+
+        let landforms: Vec<Landform> = (0..self.flattened_field.len())
+            .into_par_iter()
+            .map(|i| {
+                let x = (i % self.width) as isize;
+                let y = (i / self.width) as isize;
+                let z = self.flattened_field[i];
+
+                let mut higher = 0;
+                let mut lower = 0;
+
+                for (dx, dy) in D8_OFFSETS {
+                    let step = if dx != 0 && dy != 0 {
+                        std::f32::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+
+                    let mut max_angle = f32::NEG_INFINITY;
+                    let mut min_angle = f32::INFINITY;
+                    let mut r = 1;
+                    while r as f32 * step <= lookup_distance as f32 {
+                        let px = x + dx * r;
+                        let py = y + dy * r;
+                        if px < 0 || py < 0 || px >= width || py >= height {
+                            break;
+                        }
+
+                        let rise = self.flattened_field[(py * width + px) as usize] - z;
+                        let angle = rise.atan2(r as f32 * step * cell_size);
+                        max_angle = max_angle.max(angle);
+                        min_angle = min_angle.min(angle);
+                        r += 1;
+                    }
+
+                    if !max_angle.is_finite() {
+                        // edge of the field, nothing to compare against
+                        continue;
+                    }
+
+                    // nadir - zenith = (pi/2 + min) - (pi/2 - max)
+                    let difference = max_angle + min_angle;
+                    if difference > flatness_threshold {
+                        higher += 1;
+                    } else if difference < -flatness_threshold {
+                        lower += 1;
+                    }
+                }
+
+                Landform::classify(lower, higher)
+            })
+            .collect();
+
+        Ok(LandformMap {
+            landforms: landforms.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane_is_slope_and_level_ground_is_flat() {
+        let plane = Field::from_fn(12, 12, |x, _| x as f32 * 0.5).unwrap();
+        let landforms = plane.geomorphons(3, 0.01, 1.0).unwrap();
+        for y in 3..9 {
+            for x in 3..9 {
+                assert_eq!(landforms.get(x, y), Landform::Slope);
+            }
+        }
+
+        let level = Field::from_fn(12, 12, |_, _| 2.0).unwrap();
+        let landforms = level.geomorphons(3, 0.01, 1.0).unwrap();
+        assert_eq!(landforms.count(Landform::Flat), 12 * 12);
+    }
+
+    #[test]
+    fn peak_and_pit() {
+        let cone = Field::from_fn(9, 9, |x, y| {
+            -((x as f32 - 4.0).powi(2) + (y as f32 - 4.0).powi(2)).sqrt()
+        })
+        .unwrap();
+        assert_eq!(
+            cone.geomorphons(3, 0.01, 1.0).unwrap().get(4, 4),
+            Landform::Peak
+        );
+
+        let bowl = cone.map(|v| -v);
+        assert_eq!(
+            bowl.geomorphons(3, 0.01, 1.0).unwrap().get(4, 4),
+            Landform::Pit
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::field::field::Field;
+use crate::field::mesh::{Mesh, UpAxis};
+use crate::field::view::Raster;
+use crate::hex::hex::Hex;
+use crate::hex::layout::Layout;
+use crate::hex::point::Point;
+
+/// Settings for `Field::hex_prisms`.
+#[derive(Debug, Clone, Copy)]
+pub struct HexPrismOptions {
+    /// world units per pixel
+    pub horizontal_scale: f32,
+    /// multiplier on field values
+    pub vertical_scale: f32,
+    /// bottom of every prism, in field units
+    pub base: f32,
+    /// chamfer around the top face as a fraction of the hex radius, 0 for sharp edges
+    pub bevel: f32,
+    pub up_axis: UpAxis,
+}
+
+impl Default for HexPrismOptions {
+    fn default() -> Self {
+        Self {
+            horizontal_scale: 1.0,
+            vertical_scale: 1.0,
+            base: 0.0,
+            bevel: 0.0,
+            up_axis: UpAxis::Y,
+        }
+    }
+}
+
+/// Look of a single prism.
+#[derive(Debug, Clone, Copy)]
+pub struct HexStyle {
+    /// linear RGB
+    pub color: [f32; 3],
+    pub material: u32,
+}
+
+impl Default for HexStyle {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 3],
+            material: 0,
+        }
+    }
+}
+
+impl Mesh {
+    /// Adds a flat convex polygon, counter-clockwise seen from the side it faces.
+    fn push_polygon(&mut self, corners: &[[f32; 3]], uvs: &[[f32; 2]], style: HexStyle) {
+        let first = self.positions.len() as u32;
+        for (&p, &uv) in corners.iter().zip(uvs) {
+            self.push_vertex(p, [0.0; 3], uv);
+            self.colors.push(style.color);
+        }
+        let normal = self.face_normal([first, first + 1, first + 2]);
+        for n in &mut self.normals[first as usize..] {
+            *n = normal;
+        }
+        for k in 1..corners.len() as u32 - 1 {
+            self.triangles.push([first, first + k, first + k + 1]);
+            self.materials.push(style.material);
+        }
+    }
+}
+
+impl Field {
+    /// One closed prism per hex of `layout`, as tall as the hex mean that `hex_aggregate`
+    /// paints, with flat-shaded walls down to `base`. `style` picks the colour and material
+    /// of each hex from its coordinates and mean.
+    pub fn hex_prisms(
+        &self,
+        layout: &Layout,
+        options: &HexPrismOptions,
+        style: impl Fn(&Hex, f32) -> HexStyle,
+    ) -> Result<Mesh, Box<dyn std::error::Error>> {
+        Self::check_cell_size(options.horizontal_scale)?;
+        if !options.vertical_scale.is_finite() {
+            return Err(format!("Invalid vertical scale: {}", options.vertical_scale).into());
+        }
+        if !options.base.is_finite() {
+            return Err(format!("Invalid prism base: {}", options.base).into());
+        }
+        if options.bevel.is_nan() || !(0.0..1.0).contains(&options.bevel) {
+            return Err(format!("Invalid bevel: {}", options.bevel).into());
+        }
+
+        let mut hexes: Vec<(Hex, f32)> = self
+            .hex_bins(layout)
+            .into_iter()
+            .filter(|(_, bin)| bin.pixel_count > 0)
+            .map(|(hex, bin)| (hex, bin.agr_value / bin.pixel_count as f32))
+            .collect();
+        hexes.sort_by_key(|&(hex, _)| hex);
+
+        let hs = options.horizontal_scale;
+        let (u_scale, v_scale) = (
+            1.0 / (self.width as f32 - 1.0).max(1.0),
+            1.0 / (self.height() as f32 - 1.0).max(1.0),
+        );
+        let uv = |p: &Point| [p.x as f32 * u_scale, p.y as f32 * v_scale];
+        let at = |p: &Point, height: f32| [p.x as f32 * hs, height, p.y as f32 * hs];
+
+        let mut mesh = Mesh::default();
+        for (hex, mean) in hexes {
+            let look = style(&hex, mean);
+            let centre = Point::from_hex(layout, &hex);
+            let mut corners = layout.polygon_corners(&hex);
+
+            // faces are wound for corners with negative signed area in (x, y) pixel space
+            let area: f64 = (0..6)
+                .map(|i| {
+                    let (a, b) = (corners[i], corners[(i + 1) % 6]);
+                    a.x * b.y - b.x * a.y
+                })
+                .sum();
+            if area > 0.0 {
+                corners.reverse();
+            }
+
+            let bottom = options.base * options.vertical_scale;
+            let top = (mean * options.vertical_scale).max(bottom);
+            let radius = corners
+                .iter()
+                .map(|c| ((c.x - centre.x).hypot(c.y - centre.y)) as f32)
+                .sum::<f32>()
+                / 6.0;
+            let drop = (options.bevel * radius * hs).min(top - bottom);
+            let shoulder = top - drop;
+            let inner: Vec<Point> = corners
+                .iter()
+                .map(|&c| centre + (c + centre * -1.0) * (1.0 - options.bevel as f64))
+                .collect();
+
+            // a prism flat on its base has no room for a chamfer, keep the full top
+            let rim = if drop > 0.0 { &inner } else { &corners };
+            let top_face: Vec<[f32; 3]> = rim.iter().map(|p| at(p, top)).collect();
+            let top_uvs: Vec<[f32; 2]> = rim.iter().map(uv).collect();
+            mesh.push_polygon(&top_face, &top_uvs, look);
+
+            for i in 0..6 {
+                let (a, b) = (&corners[i], &corners[(i + 1) % 6]);
+                if drop > 0.0 {
+                    let (ia, ib) = (&inner[i], &inner[(i + 1) % 6]);
+                    mesh.push_polygon(
+                        &[at(ia, top), at(a, shoulder), at(b, shoulder), at(ib, top)],
+                        &[uv(ia), uv(a), uv(b), uv(ib)],
+                        look,
+                    );
+                }
+                if shoulder > bottom {
+                    mesh.push_polygon(
+                        &[
+                            at(a, shoulder),
+                            at(a, bottom),
+                            at(b, bottom),
+                            at(b, shoulder),
+                        ],
+                        &[uv(a), uv(a), uv(b), uv(b)],
+                        look,
+                    );
+                }
+            }
+
+            let bottom_face: Vec<[f32; 3]> = corners.iter().rev().map(|p| at(p, bottom)).collect();
+            let bottom_uvs: Vec<[f32; 2]> = corners.iter().rev().map(uv).collect();
+            mesh.push_polygon(&bottom_face, &bottom_uvs, look);
+        }
+
+        Ok(mesh.oriented(options.up_axis))
+    }
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+impl Field {
+    /// For every cell, scans `directions` evenly spaced azimuths out to `radius` cells and hands
+    /// the highest and lowest elevation angle seen in each direction to `reduce`.
+    ///
+    /// Directions that leave the field immediately report an angle of 0 (a flat horizon).
+    fn horizon_map<F>(
+        &self,
+        directions: usize,
+        radius: usize,
+        cell_size: f32,
+        reduce: F,
+    ) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: Fn(&[f32], &[f32]) -> f32 + Sync,
+    {
+        if directions == 0 || radius == 0 {
+            return Err("Horizon scan needs at least one direction and a non-zero radius".into());
+        }
+        Self::check_cell_size(cell_size)?;
+
+        let width = self.width as f32;
+        let height = self.height() as f32;
+        let steps: Vec<(f32, f32)> = (0..directions)
+            .map(|k| {
+                let azimuth = TAU * k as f32 / directions as f32;
+                (azimuth.sin(), -azimuth.cos())
+            })
+            .collect();
+
+        let result: Vec<f32> = (0..self.flattened_field.len())
+            .into_par_iter()
+            .map_init(
+                || (vec![0.0; directions], vec![0.0; directions]),
+                |(highest, lowest), i| {
+                    let x = (i % self.width) as f32;
+                    let y = (i / self.width) as f32;
+                    let z = self.flattened_field[i];
+
+                    for (k, &(sx, sy)) in steps.iter().enumerate() {
+                        let mut max_angle = f32::NEG_INFINITY;
+                        let mut min_angle = f32::INFINITY;
+
+                        for r in 1..=radius {
+                            let px = x + sx * r as f32;
+                            let py = y + sy * r as f32;
+                            if px < 0.0 || py < 0.0 || px > width - 1.0 || py > height - 1.0 {
+                                break;
+                            }
+
+                            let rise = self.sample_bilinear(px, py) - z;
+                            let angle = rise.atan2(r as f32 * cell_size);
+                            max_angle = max_angle.max(angle);
+                            min_angle = min_angle.min(angle);
+                        }
+
+                        highest[k] = if max_angle.is_finite() {
+                            max_angle
+                        } else {
+                            0.0
+                        };
+                        lowest[k] = if min_angle.is_finite() {
+                            min_angle
+                        } else {
+                            0.0
+                        };
+                    }
+
+                    reduce(highest, lowest)
+                },
+            )
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Positive topographic openness (Yokoyama et al. 2002), the mean zenith angle
+    /// to the horizon in radians. High on ridges and peaks.
+    pub fn positive_openness(
+        &self,
+        directions: usize,
+        radius: usize,
+        cell_size: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.horizon_map(directions, radius, cell_size, |highest, _| {
+            highest.iter().map(|&a| FRAC_PI_2 - a).sum::<f32>() / highest.len() as f32
+        })
+    }
+
+    /// Negative topographic openness, the mean nadir angle in radians. High in valleys and pits.
+    pub fn negative_openness(
+        &self,
+        directions: usize,
+        radius: usize,
+        cell_size: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.horizon_map(directions, radius, cell_size, |_, lowest| {
+            lowest.iter().map(|&a| FRAC_PI_2 + a).sum::<f32>() / lowest.len() as f32
+        })
+    }
+
+    /// Sky-view factor (Zaksek et al. 2011), the visible fraction of the sky hemisphere in [0, 1].
+    pub fn sky_view_factor(
+        &self,
+        directions: usize,
+        radius: usize,
+        cell_size: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.horizon_map(directions, radius, cell_size, |highest, _| {
+            1.0 - highest.iter().map(|&a| a.max(0.0).sin()).sum::<f32>() / highest.len() as f32
+        })
+    }
+
+    /// Cosine-weighted ambient occlusion in [0, 1], 1 being fully unoccluded.
+    ///
+    /// A horizon at elevation g hides sin^2(g) of the cosine-weighted sky in its sector,
+    /// so unlike the sky-view factor low horizons barely darken the result.
+    pub fn ambient_occlusion(
+        &self,
+        directions: usize,
+        radius: usize,
+        cell_size: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.horizon_map(directions, radius, cell_size, |highest, _| {
+            1.0 - highest
+                .iter()
+                .map(|&a| a.max(0.0).sin().powi(2))
+                .sum::<f32>()
+                / highest.len() as f32
+        })
+    }
+}
@@ -0,0 +1,411 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::seamless::Boundary;
+use crate::field::view::Raster;
+
+/// (dx, dy) of the 8 neighbours, clockwise starting north-west
+pub(crate) const D8_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+];
+
+// Mitasova et al. (1996) exponents for the LS-factor
+const LS_AREA_EXPONENT: f32 = 0.4;
+const LS_SLOPE_EXPONENT: f32 = 1.3;
+
+// keeps ln(a / tan b) finite on perfectly flat cells
+const MIN_TAN_SLOPE: f32 = 1e-4;
+
+/// min-heap entry for the priority flood
+#[derive(PartialEq)]
+pub(crate) struct Cell {
+    pub height: f32,
+    pub index: usize,
+}
+
+impl Eq for Cell {}
+
+impl Ord for Cell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so BinaryHeap pops the lowest cell first
+        other
+            .height
+            .total_cmp(&self.height)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Field {
+    pub(crate) fn neighbor(&self, i: usize, offset: (isize, isize)) -> Option<usize> {
+        let x = (i % self.width) as isize + offset.0;
+        let y = (i / self.width) as isize + offset.1;
+
+        if x >= 0 && x < self.width as isize && y >= 0 && y < self.height() as isize {
+            Some(y as usize * self.width + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn check_cell_size(cell_size: f32) -> Result<(), Box<dyn std::error::Error>> {
+        if cell_size > 0.0 && cell_size.is_finite() {
+            Ok(())
+        } else {
+            Err(format!("Invalid cell size: {}", cell_size).into())
+        }
+    }
+
+    /// Steepest-descent (D8) receiver of every cell, `None` for cells that drain off the field.
+    pub(crate) fn d8_receivers(&self) -> Vec<Option<usize>> {
+        (0..self.flattened_field.len())
+            .into_par_iter()
+            .map(|i| {
+                let mut receiver = None;
+                let mut steepest = 0.0;
+
+                for offset in D8_OFFSETS {
+                    let Some(n) = self.neighbor(i, offset) else {
+                        continue;
+                    };
+                    let distance = if offset.0 != 0 && offset.1 != 0 {
+                        std::f32::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+                    let drop = (self.flattened_field[i] - self.flattened_field[n]) / distance;
+                    if drop > steepest {
+                        steepest = drop;
+                        receiver = Some(n);
+                    }
+                }
+
+                receiver
+            })
+            .collect()
+    }
+
+    /// cell indices ordered from highest to lowest
+    fn descending_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.flattened_field.len()).collect();
+        order.par_sort_unstable_by(|&a, &b| {
+            self.flattened_field[b].total_cmp(&self.flattened_field[a])
+        });
+        order
+    }
+}
+
+/// See `Raster::slope`.
+pub(crate) fn slope<R: Raster + ?Sized>(
+    raster: &R,
+    cell_size: f32,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    Field::check_cell_size(cell_size)?;
+
+    let width = raster.width();
+    let result: Vec<f32> = (0..width * raster.height())
+        .into_par_iter()
+        .map(|i| {
+            let x = (i % width) as isize;
+            let y = (i / width) as isize;
+
+            let a = raster.sample(x - 1, y - 1);
+            let b = raster.sample(x, y - 1);
+            let c = raster.sample(x + 1, y - 1);
+            let d = raster.sample(x - 1, y);
+            let f = raster.sample(x + 1, y);
+            let g = raster.sample(x - 1, y + 1);
+            let h = raster.sample(x, y + 1);
+            let k = raster.sample(x + 1, y + 1);
+
+            let dz_dx = ((c + 2.0 * f + k) - (a + 2.0 * d + g)) / (8.0 * cell_size);
+            let dz_dy = ((g + 2.0 * h + k) - (a + 2.0 * b + c)) / (8.0 * cell_size);
+
+            (dz_dx * dz_dx + dz_dy * dz_dy).sqrt().atan()
+        })
+        .collect();
+
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+/// See `Raster::fill_depressions`.
+pub(crate) fn fill_depressions<R: Raster + ?Sized>(
+    raster: &R,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    if raster.boundary() == Boundary::Wrap {
+        return Err(
+            "Depressions cannot be filled on a wrapped field, it has no edge to drain to".into(),
+        );
+    }
+
+    let mut filled = raster.to_field();
+    let (width, height) = (filled.width, filled.height());
+    let mut closed = vec![false; filled.flattened_field.len()];
+    let mut open = BinaryHeap::new();
+
+    for (i, is_closed) in closed.iter_mut().enumerate() {
+        let x = i % width;
+        let y = i / width;
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            *is_closed = true;
+            open.push(Cell {
+                height: filled.flattened_field[i],
+                index: i,
+            });
+        }
+    }
+
+    while let Some(cell) = open.pop() {
+        for offset in D8_OFFSETS {
+            let Some(n) = filled.neighbor(cell.index, offset) else {
+                continue;
+            };
+            if closed[n] {
+                continue;
+            }
+            closed[n] = true;
+
+            if filled.flattened_field[n] <= cell.height {
+                filled.flattened_field[n] = cell.height.next_up();
+            }
+            open.push(Cell {
+                height: filled.flattened_field[n],
+                index: n,
+            });
+        }
+    }
+
+    Ok(filled)
+}
+
+/// See `Raster::flow_accumulation`.
+pub(crate) fn flow_accumulation<R: Raster + ?Sized>(
+    raster: &R,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    let filled = fill_depressions(raster)?;
+    let receivers = filled.d8_receivers();
+
+    let mut accumulation = vec![1.0_f32; filled.flattened_field.len()];
+    for i in filled.descending_order() {
+        if let Some(r) = receivers[i] {
+            accumulation[r] += accumulation[i];
+        }
+    }
+
+    Ok(Field {
+        flattened_field: accumulation.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+/// specific catchment area (upslope area per unit contour width) and tan(slope) per cell
+fn catchment_and_slope<R: Raster + ?Sized>(
+    raster: &R,
+    cell_size: f32,
+) -> Result<(Vec<f32>, Vec<f32>), Box<dyn std::error::Error>> {
+    let slope = slope(raster, cell_size)?;
+    let accumulation = flow_accumulation(raster)?;
+
+    let catchment = accumulation
+        .flattened_field
+        .par_iter()
+        .map(|&cells| cells * cell_size)
+        .collect();
+
+    let tan_slope = slope
+        .flattened_field
+        .par_iter()
+        .map(|&beta| beta.tan())
+        .collect();
+
+    Ok((catchment, tan_slope))
+}
+
+/// See `Raster::topographic_wetness_index`.
+pub(crate) fn topographic_wetness_index<R: Raster + ?Sized>(
+    raster: &R,
+    cell_size: f32,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    let (catchment, tan_slope) = catchment_and_slope(raster, cell_size)?;
+
+    let result: Vec<f32> = catchment
+        .par_iter()
+        .zip(tan_slope.par_iter())
+        .map(|(&a, &tan_b)| (a / tan_b.max(MIN_TAN_SLOPE)).ln())
+        .collect();
+
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+/// See `Raster::stream_power_index`.
+pub(crate) fn stream_power_index<R: Raster + ?Sized>(
+    raster: &R,
+    cell_size: f32,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    let (catchment, tan_slope) = catchment_and_slope(raster, cell_size)?;
+
+    let result: Vec<f32> = catchment
+        .par_iter()
+        .zip(tan_slope.par_iter())
+        .map(|(&a, &tan_b)| a * tan_b)
+        .collect();
+
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+/// See `Raster::ls_factor`.
+pub(crate) fn ls_factor<R: Raster + ?Sized>(
+    raster: &R,
+    cell_size: f32,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    let slope = slope(raster, cell_size)?;
+    let accumulation = flow_accumulation(raster)?;
+
+    let result: Vec<f32> = accumulation
+        .flattened_field
+        .par_iter()
+        .zip(slope.flattened_field.par_iter())
+        .map(|(&cells, &beta)| {
+            let a = cells * cell_size;
+            (LS_AREA_EXPONENT + 1.0)
+                * (a / 22.13).powf(LS_AREA_EXPONENT)
+                * (beta.sin() / 0.0896).powf(LS_SLOPE_EXPONENT)
+        })
+        .collect();
+
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+/// See `Raster::height_above_nearest_drainage`.
+pub(crate) fn height_above_nearest_drainage<R: Raster + ?Sized>(
+    raster: &R,
+    channel_threshold: f32,
+) -> Result<Field, Box<dyn std::error::Error>> {
+    if channel_threshold.is_nan() || channel_threshold < 1.0 {
+        return Err(format!("Invalid channel threshold: {}", channel_threshold).into());
+    }
+
+    let filled = fill_depressions(raster)?;
+    let receivers = filled.d8_receivers();
+    let order = filled.descending_order();
+
+    let mut accumulation = vec![1.0_f32; filled.flattened_field.len()];
+    for &i in order.iter() {
+        if let Some(r) = receivers[i] {
+            accumulation[r] += accumulation[i];
+        }
+    }
+
+    // walk lowest to highest so each receiver is resolved before its donors
+    let mut drain = vec![0_usize; filled.flattened_field.len()];
+    for &i in order.iter().rev() {
+        drain[i] = match receivers[i] {
+            Some(r) if accumulation[i] < channel_threshold => drain[r],
+            _ => i,
+        };
+    }
+
+    let result: Vec<f32> = drain
+        .par_iter()
+        .enumerate()
+        .map(|(i, &d)| filled.flattened_field[i] - filled.flattened_field[d])
+        .collect();
+
+    Ok(Field {
+        flattened_field: result.into_boxed_slice(),
+        width: raster.width(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pit_is_filled_to_a_draining_surface() {
+        // a basin walled in at 10 apart from one notch at 1 in the top edge
+        let field = Field::from_fn(7, 7, |x, y| match (x, y) {
+            (3, 0) => 1.0,
+            (0 | 6, _) | (_, 0 | 6) => 10.0,
+            _ => 0.0,
+        })
+        .unwrap();
+        let filled = field.fill_depressions().unwrap();
+        let receivers = filled.d8_receivers();
+
+        for y in 1..6 {
+            for x in 1..6 {
+                let i = y * 7 + x;
+                assert!(filled.flattened_field[i] > 1.0);
+                let r = receivers[i].expect("interior cell without a lower neighbour");
+                assert!(filled.flattened_field[r] < filled.flattened_field[i]);
+            }
+        }
+        assert_eq!(filled.get(3, 0), 1.0);
+        assert_eq!(filled.get(0, 3), 10.0);
+    }
+
+    #[test]
+    fn tilted_plane_accumulation_sums_to_cell_count() {
+        let (width, height) = (9, 6);
+        let plane = Field::from_fn(width, height, |_, y| y as f32).unwrap();
+        let accumulation = plane.flow_accumulation().unwrap();
+        let receivers = plane.fill_depressions().unwrap().d8_receivers();
+
+        let outlets: f32 = (0..width * height)
+            .filter(|&i| receivers[i].is_none())
+            .map(|i| accumulation.flattened_field[i])
+            .sum();
+        assert_eq!(outlets, (width * height) as f32);
+        assert!((0..width).all(|x| accumulation.get(x, 0) == height as f32));
+    }
+
+    #[test]
+    fn hand_is_zero_on_channels() {
+        // a V-shaped valley along x = 4 draining towards the top edge
+        let valley = Field::from_fn(9, 12, |x, y| (x as f32 - 4.0).abs() + y as f32 * 0.1);
+        let valley = valley.unwrap();
+        let threshold = 5.0;
+        let hand = valley.height_above_nearest_drainage(threshold).unwrap();
+        let accumulation = valley.flow_accumulation().unwrap();
+
+        let mut channels = 0;
+        for (i, &a) in accumulation.flattened_field.iter().enumerate() {
+            if a >= threshold {
+                assert_eq!(hand.flattened_field[i], 0.0);
+                channels += 1;
+            }
+        }
+        assert!(channels > 0);
+        assert!(hand.flattened_field.iter().all(|&h| h >= 0.0));
+        assert!(hand.get(0, 6) > 3.0);
+        assert!(valley.height_above_nearest_drainage(0.5).is_err());
+    }
+}
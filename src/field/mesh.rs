@@ -0,0 +1,664 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpAxis {
+    /// glTF and most DCC tools
+    Y,
+    /// slicers and CAD
+    Z,
+}
+
+/// What happens around the outline of a heightfield mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshEdges {
+    /// open surface
+    None,
+    /// walls hanging this far below every edge vertex, hides cracks between tiles
+    Skirt(f32),
+    /// walls down to a flat bottom this far below the lowest point, a closed solid for printing
+    Base(f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshOptions {
+    /// world units between neighbouring cells
+    pub horizontal_scale: f32,
+    /// multiplier on field values
+    pub vertical_scale: f32,
+    /// take every nth cell in each direction, the last row and column are always kept
+    pub stride: usize,
+    pub edges: MeshEdges,
+    pub up_axis: UpAxis,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        Self {
+            horizontal_scale: 1.0,
+            vertical_scale: 1.0,
+            stride: 1,
+            edges: MeshEdges::None,
+            up_axis: UpAxis::Y,
+        }
+    }
+}
+
+/// Indexed triangle mesh, counter-clockwise seen from outside. UVs have their origin at the
+/// top-left like glTF, the OBJ and PLY writers flip them.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// linear RGB per vertex, or empty
+    pub colors: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+    /// material id per triangle, or empty for a single material
+    pub materials: Vec<u32>,
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        v.map(|c| c / length)
+    } else {
+        v
+    }
+}
+
+/// Linear channel value to the sRGB transfer curve, clamped to [0, 1].
+fn srgb_encode(linear: f32) -> f32 {
+    let c = linear.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for k in 0..4 {
+            if k <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * k) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+impl Mesh {
+    /// Appends a vertex and returns its index.
+    pub fn push_vertex(&mut self, position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        self.positions.len() as u32 - 1
+    }
+
+    pub fn face_normal(&self, triangle: [u32; 3]) -> [f32; 3] {
+        let [a, b, c] = triangle.map(|i| self.positions[i as usize]);
+        normalize(cross(sub(b, a), sub(c, a)))
+    }
+
+    /// Replaces the normals with area-weighted averages of the adjacent face normals.
+    pub fn recompute_normals(&mut self) {
+        let mut normals = vec![[0.0_f32; 3]; self.positions.len()];
+        for t in &self.triangles {
+            let [a, b, c] = t.map(|i| self.positions[i as usize]);
+            // unnormalized, so larger faces count more
+            let n = cross(sub(b, a), sub(c, a));
+            for &i in t {
+                for k in 0..3 {
+                    normals[i as usize][k] += n[k];
+                }
+            }
+        }
+        self.normals = normals.into_iter().map(normalize).collect();
+    }
+
+    /// Rotates a Y-up mesh so `up_axis` points up, keeping it right-handed.
+    pub(crate) fn oriented(mut self, up_axis: UpAxis) -> Self {
+        if up_axis == UpAxis::Z {
+            let rotate = |p: &mut [f32; 3]| *p = [p[0], -p[2], p[1]];
+            self.positions.iter_mut().for_each(rotate);
+            self.normals.iter_mut().for_each(rotate);
+        }
+        self
+    }
+
+    /// Triangle indices grouped by material id, in ascending id order.
+    fn material_groups(&self) -> Vec<(u32, Vec<[u32; 3]>)> {
+        if self.materials.is_empty() {
+            return vec![(0, self.triangles.clone())];
+        }
+        let mut groups: Vec<(u32, Vec<[u32; 3]>)> = Vec::new();
+        let mut order: Vec<usize> = (0..self.triangles.len()).collect();
+        order.sort_by_key(|&i| self.materials[i]);
+        for i in order {
+            let id = self.materials[i];
+            match groups.last_mut() {
+                Some((last, triangles)) if *last == id => triangles.push(self.triangles[i]),
+                _ => groups.push((id, vec![self.triangles[i]])),
+            }
+        }
+        groups
+    }
+
+    /// Wavefront OBJ. Vertex colours go on the `v` lines sRGB encoded, since viewers read
+    /// them as display values, and material ids become `material_<id>` groups in a `.mtl`
+    /// written next to the file.
+    pub fn write_obj(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        if !self.materials.is_empty() {
+            let library = path.with_extension("mtl");
+            let mut mtl = BufWriter::new(File::create(&library)?);
+            for (id, _) in self.material_groups() {
+                writeln!(mtl, "newmtl material_{}\nKd 1 1 1", id)?;
+            }
+            mtl.flush()?;
+            let name = library.file_name().unwrap_or_default().to_string_lossy();
+            writeln!(out, "mtllib {}", name)?;
+        }
+        for (i, p) in self.positions.iter().enumerate() {
+            match self.colors.get(i) {
+                Some(c) => {
+                    let [r, g, b] = c.map(srgb_encode);
+                    writeln!(out, "v {} {} {} {} {} {}", p[0], p[1], p[2], r, g, b)?
+                }
+                None => writeln!(out, "v {} {} {}", p[0], p[1], p[2])?,
+            }
+        }
+        for uv in &self.uvs {
+            writeln!(out, "vt {} {}", uv[0], 1.0 - uv[1])?;
+        }
+        for n in &self.normals {
+            writeln!(out, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+        for (id, triangles) in self.material_groups() {
+            if !self.materials.is_empty() {
+                writeln!(out, "usemtl material_{}", id)?;
+            }
+            for t in triangles {
+                let [a, b, c] = t.map(|i| i + 1);
+                writeln!(out, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Binary STL, facet normals only.
+    pub fn write_stl(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&[0_u8; 80])?;
+        out.write_u32::<LittleEndian>(self.triangles.len() as u32)?;
+        for &t in &self.triangles {
+            for c in self.face_normal(t) {
+                out.write_f32::<LittleEndian>(c)?;
+            }
+            for i in t {
+                for c in self.positions[i as usize] {
+                    out.write_f32::<LittleEndian>(c)?;
+                }
+            }
+            out.write_u16::<LittleEndian>(0)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Binary little-endian PLY with normals, texture coordinates and any vertex colours,
+    /// which are sRGB encoded since 8-bit PLY colours are read as display values.
+    pub fn write_ply(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let has_colors = !self.colors.is_empty();
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(
+            out,
+            "ply\nformat binary_little_endian 1.0\nelement vertex {}\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property float nx\nproperty float ny\nproperty float nz\n\
+             property float s\nproperty float t\n{}\
+             element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+            self.positions.len(),
+            if has_colors {
+                "property uchar red\nproperty uchar green\nproperty uchar blue\n"
+            } else {
+                ""
+            },
+            self.triangles.len()
+        )?;
+        for i in 0..self.positions.len() {
+            for c in self.positions[i].iter().chain(self.normals[i].iter()) {
+                out.write_f32::<LittleEndian>(*c)?;
+            }
+            out.write_f32::<LittleEndian>(self.uvs[i][0])?;
+            out.write_f32::<LittleEndian>(1.0 - self.uvs[i][1])?;
+            if has_colors {
+                for c in self.colors[i] {
+                    out.write_u8((srgb_encode(c) * 255.0).round() as u8)?;
+                }
+            }
+        }
+        for t in &self.triangles {
+            out.write_u8(3)?;
+            for &i in t {
+                out.write_u32::<LittleEndian>(i)?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// glTF 2.0 buffer and JSON, the buffer URI is left to the caller. Every material id
+    /// gets its own primitive.
+    fn gltf_parts(&self, buffer_uri: Option<&str>) -> (String, Vec<u8>) {
+        let mut buffer = Vec::new();
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut push =
+            |values: &mut dyn Iterator<Item = [u8; 4]>, count: usize, kind: &str, extra: String| {
+                let start = buffer.len();
+                values.for_each(|bytes| buffer.extend_from_slice(&bytes));
+                let (component, target) = if kind == "SCALAR" {
+                    (5125, 34963)
+                } else {
+                    (5126, 34962)
+                };
+                views.push(format!(
+                    r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+                    start,
+                    buffer.len() - start,
+                    target
+                ));
+                accessors.push(format!(
+                    r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}"{}}}"#,
+                    views.len() - 1,
+                    component,
+                    count,
+                    kind,
+                    extra
+                ));
+                accessors.len() - 1
+            };
+
+        let (min, max) =
+            self.positions
+                .iter()
+                .fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), p| {
+                    for k in 0..3 {
+                        min[k] = min[k].min(p[k]);
+                        max[k] = max[k].max(p[k]);
+                    }
+                    (min, max)
+                });
+        let bounds = format!(
+            r#","min":[{},{},{}],"max":[{},{},{}]"#,
+            min[0], min[1], min[2], max[0], max[1], max[2]
+        );
+
+        let count = self.positions.len();
+        let mut attributes = format!(
+            r#""POSITION":{},"NORMAL":{},"TEXCOORD_0":{}"#,
+            push(
+                &mut self.positions.iter().flatten().map(|c| c.to_le_bytes()),
+                count,
+                "VEC3",
+                bounds
+            ),
+            push(
+                &mut self.normals.iter().flatten().map(|c| c.to_le_bytes()),
+                count,
+                "VEC3",
+                String::new()
+            ),
+            push(
+                &mut self.uvs.iter().flatten().map(|c| c.to_le_bytes()),
+                count,
+                "VEC2",
+                String::new()
+            ),
+        );
+        if !self.colors.is_empty() {
+            let colors = push(
+                &mut self.colors.iter().flatten().map(|c| c.to_le_bytes()),
+                count,
+                "VEC3",
+                String::new(),
+            );
+            attributes += &format!(r#","COLOR_0":{}"#, colors);
+        }
+
+        let groups = self.material_groups();
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        for (id, triangles) in &groups {
+            let indices = push(
+                &mut triangles.iter().flatten().map(|i| i.to_le_bytes()),
+                triangles.len() * 3,
+                "SCALAR",
+                String::new(),
+            );
+            if self.materials.is_empty() {
+                primitives.push(format!(
+                    r#"{{"attributes":{{{}}},"indices":{}}}"#,
+                    attributes, indices
+                ));
+            } else {
+                primitives.push(format!(
+                    r#"{{"attributes":{{{}}},"indices":{},"material":{}}}"#,
+                    attributes,
+                    indices,
+                    materials.len()
+                ));
+                materials.push(format!(r#"{{"name":"material_{}"}}"#, id));
+            }
+        }
+
+        let uri = buffer_uri.map_or(String::new(), |uri| format!(r#","uri":"{}""#, uri));
+        let materials = if materials.is_empty() {
+            String::new()
+        } else {
+            format!(r#""materials":[{}],"#, materials.join(","))
+        };
+        let json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0","generator":"probable-eureka"}},"#,
+                r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+                r#""meshes":[{{"primitives":[{}]}}],{}"#,
+                r#""buffers":[{{"byteLength":{}{}}}],"#,
+                r#""bufferViews":[{}],"accessors":[{}]}}"#
+            ),
+            primitives.join(","),
+            materials,
+            buffer.len(),
+            uri,
+            views.join(","),
+            accessors.join(","),
+        );
+
+        (json, buffer)
+    }
+
+    /// glTF 2.0. A `.glb` path gets the binary container, anything else a `.gltf` JSON file
+    /// with the buffer embedded as base64. Vertex colours stay linear, as `COLOR_0` requires.
+    pub fn write_gltf(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let binary = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+        let mut out = BufWriter::new(File::create(path)?);
+
+        if !binary {
+            let (_, buffer) = self.gltf_parts(None);
+            let uri = format!("data:application/octet-stream;base64,{}", base64(&buffer));
+            let (json, _) = self.gltf_parts(Some(&uri));
+            out.write_all(json.as_bytes())?;
+            out.flush()?;
+            return Ok(());
+        }
+
+        let (json, mut buffer) = self.gltf_parts(None);
+        let mut json = json.into_bytes();
+        // chunks are 4-byte aligned, JSON padded with spaces and the buffer with zeros
+        json.resize(json.len().next_multiple_of(4), b' ');
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+        out.write_all(b"glTF")?;
+        out.write_u32::<LittleEndian>(2)?;
+        out.write_u32::<LittleEndian>((12 + 8 + json.len() + 8 + buffer.len()) as u32)?;
+        out.write_u32::<LittleEndian>(json.len() as u32)?;
+        out.write_all(b"JSON")?;
+        out.write_all(&json)?;
+        out.write_u32::<LittleEndian>(buffer.len() as u32)?;
+        out.write_all(b"BIN\0")?;
+        out.write_all(&buffer)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Field {
+    /// Triangulated heightfield, two triangles per grid square, with smooth vertex normals.
+    pub fn to_mesh(&self, options: &MeshOptions) -> Result<Mesh, Box<dyn std::error::Error>> {
+        let width = self.width;
+        let height = self.height();
+        if width < 2 || height < 2 {
+            return Err("A mesh needs a field of at least 2x2".into());
+        }
+        if options.stride == 0 {
+            return Err("Mesh stride must be at least one".into());
+        }
+        Self::check_cell_size(options.horizontal_scale)?;
+        if !options.vertical_scale.is_finite() {
+            return Err(format!("Invalid vertical scale: {}", options.vertical_scale).into());
+        }
+        match options.edges {
+            MeshEdges::Skirt(d) | MeshEdges::Base(d) if d.is_nan() || d < 0.0 => {
+                return Err(format!("Invalid skirt or base depth: {}", d).into());
+            }
+            _ => {}
+        }
+
+        let samples = |n: usize| {
+            let mut s: Vec<usize> = (0..n).step_by(options.stride).collect();
+            if s.last() != Some(&(n - 1)) {
+                s.push(n - 1);
+            }
+            s
+        };
+        let xs = samples(width);
+        let ys = samples(height);
+        let (hs, vs) = (options.horizontal_scale, options.vertical_scale);
+
+        let position = |x: usize, y: usize| [x as f32 * hs, self.get(x, y) * vs, y as f32 * hs];
+        let uv = |x: usize, y: usize| {
+            [
+                x as f32 / (width - 1) as f32,
+                y as f32 / (height - 1) as f32,
+            ]
+        };
+
+        let top: Vec<([f32; 3], [f32; 3], [f32; 2])> = ys
+            .par_iter()
+            .flat_map_iter(|&y| {
+                xs.iter().map(move |&x| {
+                    let (xi, yi) = (x as isize, y as isize);
+                    let dx = (self.sample(xi + 1, yi) - self.sample(xi - 1, yi)) * vs / (2.0 * hs);
+                    let dz = (self.sample(xi, yi + 1) - self.sample(xi, yi - 1)) * vs / (2.0 * hs);
+                    (position(x, y), normalize([-dx, 1.0, -dz]), uv(x, y))
+                })
+            })
+            .collect();
+
+        let mut mesh = Mesh::default();
+        for (p, n, t) in top {
+            mesh.push_vertex(p, n, t);
+        }
+
+        let columns = xs.len() as u32;
+        for j in 0..ys.len() as u32 - 1 {
+            for i in 0..columns - 1 {
+                let top_left = j * columns + i;
+                let bottom_left = top_left + columns;
+                mesh.triangles.push([top_left, bottom_left, top_left + 1]);
+                mesh.triangles
+                    .push([top_left + 1, bottom_left, bottom_left + 1]);
+            }
+        }
+
+        if options.edges != MeshEdges::None {
+            self.add_walls(&mut mesh, &xs, &ys, options);
+        }
+
+        Ok(mesh.oriented(options.up_axis))
+    }
+
+    fn add_walls(&self, mesh: &mut Mesh, xs: &[usize], ys: &[usize], options: &MeshOptions) {
+        let (last_x, last_y) = (*xs.last().unwrap(), *ys.last().unwrap());
+
+        // clockwise seen from above: top row, right column, bottom row, left column
+        let mut outline: Vec<(usize, usize)> = xs.iter().map(|&x| (x, 0)).collect();
+        outline.extend(ys[1..].iter().map(|&y| (last_x, y)));
+        outline.extend(xs.iter().rev().skip(1).map(|&x| (x, last_y)));
+        outline.extend(ys.iter().rev().skip(1).take(ys.len() - 2).map(|&y| (0, y)));
+
+        let (hs, vs) = (options.horizontal_scale, options.vertical_scale);
+        let floor = match options.edges {
+            MeshEdges::Base(thickness) => {
+                let lowest = self
+                    .flattened_field
+                    .iter()
+                    .fold(f32::MAX, |m, &v| m.min(v * vs));
+                Some(lowest - thickness)
+            }
+            _ => None,
+        };
+        let depth = match options.edges {
+            MeshEdges::Skirt(depth) => depth,
+            _ => 0.0,
+        };
+
+        let (width, height) = (self.width as f32 - 1.0, self.height() as f32 - 1.0);
+        let uv = |x: usize, y: usize| [x as f32 / width, y as f32 / height];
+        let top = |x: usize, y: usize| [x as f32 * hs, self.get(x, y) * vs, y as f32 * hs];
+        let bottom = |x: usize, y: usize| {
+            let p = top(x, y);
+            [p[0], floor.unwrap_or(p[1] - depth), p[2]]
+        };
+
+        let mut bottom_ring = Vec::with_capacity(outline.len());
+        for k in 0..outline.len() {
+            let (ax, ay) = outline[k];
+            let (bx, by) = outline[(k + 1) % outline.len()];
+            let (ta, tb, ba, bb) = (top(ax, ay), top(bx, by), bottom(ax, ay), bottom(bx, by));
+
+            // outward is to the left of the direction of travel when seen from above
+            let along = normalize(sub(tb, ta));
+            let normal = normalize([along[2], 0.0, -along[0]]);
+
+            let a = mesh.push_vertex(ta, normal, uv(ax, ay));
+            let b = mesh.push_vertex(tb, normal, uv(bx, by));
+            let a_low = mesh.push_vertex(ba, normal, uv(ax, ay));
+            let b_low = mesh.push_vertex(bb, normal, uv(bx, by));
+            mesh.triangles.push([a, b, a_low]);
+            mesh.triangles.push([b, b_low, a_low]);
+            bottom_ring.push((ba, uv(ax, ay)));
+        }
+
+        if let Some(floor) = floor {
+            // fan from the centre so the bottom shares every wall vertex and stays watertight
+            let down = [0.0, -1.0, 0.0];
+            let centre = mesh.push_vertex(
+                [width * hs / 2.0, floor, height * hs / 2.0],
+                down,
+                [0.5, 0.5],
+            );
+            let first = mesh.positions.len() as u32;
+            for &(p, t) in &bottom_ring {
+                mesh.push_vertex(p, down, t);
+            }
+            let n = bottom_ring.len() as u32;
+            for k in 0..n {
+                mesh.triangles
+                    .push([centre, first + k, first + (k + 1) % n]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashbrown::HashMap;
+
+    fn hill() -> Field {
+        Field::from_fn(9, 6, |x, y| {
+            4.0 - (x as f32 - 4.0).abs() * 0.5 + y as f32 * 0.1
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn grid_counts_follow_the_stride() {
+        let mesh = hill().to_mesh(&MeshOptions::default()).unwrap();
+        assert_eq!(mesh.positions.len(), 9 * 6);
+        assert_eq!(mesh.triangles.len(), 2 * 8 * 5);
+
+        // every third cell plus the last row and column: 4 x 3 samples
+        let options = MeshOptions {
+            stride: 3,
+            ..MeshOptions::default()
+        };
+        let coarse = hill().to_mesh(&options).unwrap();
+        assert_eq!(coarse.positions.len(), 4 * 3);
+        assert_eq!(coarse.triangles.len(), 2 * 3 * 2);
+        assert!(coarse.positions.contains(&[8.0, hill().get(8, 5), 5.0]));
+    }
+
+    #[test]
+    fn base_makes_a_closed_consistently_wound_solid() {
+        for up_axis in [UpAxis::Y, UpAxis::Z] {
+            let options = MeshOptions {
+                edges: MeshEdges::Base(1.0),
+                up_axis,
+                ..MeshOptions::default()
+            };
+            let mesh = hill().to_mesh(&options).unwrap();
+
+            // walls have their own vertices for flat normals, so weld by position; then each
+            // directed edge appears once and is always matched by its reverse
+            let at = |i: u32| mesh.positions[i as usize].map(f32::to_bits);
+            let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+            for t in &mesh.triangles {
+                for k in 0..3 {
+                    *edges.entry((at(t[k]), at(t[(k + 1) % 3]))).or_default() += 1;
+                }
+            }
+            assert!(edges.values().all(|&n| n == 1));
+            assert!(edges.keys().all(|&(a, b)| edges.contains_key(&(b, a))));
+
+            // counter-clockwise from outside encloses a positive volume
+            let volume: f32 = mesh
+                .triangles
+                .iter()
+                .map(|t| {
+                    let [a, b, c] = t.map(|i| mesh.positions[i as usize]);
+                    let n = cross(b, c);
+                    (a[0] * n[0] + a[1] * n[1] + a[2] * n[2]) / 6.0
+                })
+                .sum();
+            assert!(volume > 0.0);
+        }
+    }
+}
@@ -1 +1,30 @@
-pub mod field;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod field;
+pub mod compare;
+pub mod components;
+pub mod contours;
+pub mod critical_points;
+pub mod distance;
+pub mod erosion;
+pub mod expression;
+pub mod fft;
+pub mod geomorphon;
+pub mod hex_mesh;
+pub mod horizon;
+pub mod hydrology;
+pub mod mesh;
+pub mod noise;
+pub mod normal_map;
+pub mod ops;
+pub mod pyramid;
+pub mod remap;
+pub mod resample;
+pub mod rng;
+pub mod seamless;
+pub mod splat;
+pub mod stats;
+pub mod thermal;
+pub mod tiles;
+pub mod tin;
+pub mod view;
+pub mod visibility;
\ No newline at end of file
@@ -0,0 +1,474 @@
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::rng::SplitMix64;
+use crate::field::view::Raster;
+
+// skew factors for 2D simplex noise, (sqrt(3) - 1) / 2 and (3 - sqrt(3)) / 6
+const SIMPLEX_F2: f32 = 0.366_025_42;
+const SIMPLEX_G2: f32 = 0.211_324_87;
+
+// Musgrave's ridged multifractal constants
+const RIDGE_OFFSET: f32 = 1.0;
+const RIDGE_GAIN: f32 = 2.0;
+
+/// Single-octave noise functions, all sampled in lattice units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseBasis {
+    /// random lattice values, quintic interpolation, in [-1, 1]
+    Value,
+    /// gradient noise, roughly [-1, 1]
+    Perlin,
+    /// Gustavson's 2D simplex noise, roughly [-1, 1]
+    Simplex,
+    /// distance to the nearest feature point, 0 on the points and rarely above 1
+    Worley,
+    /// random value of the nearest feature point's cell, flat Voronoi plates in [-1, 1]
+    Voronoi,
+}
+
+/// Octave stack for `fbm` and `ridged_multifractal`.
+#[derive(Debug, Clone, Copy)]
+pub struct Fractal {
+    pub octaves: usize,
+    /// frequency multiplier between octaves
+    pub lacunarity: f32,
+    /// amplitude multiplier between octaves
+    pub gain: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Self {
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+fn hash(x: i32, y: i32, seed: u64) -> u64 {
+    let lattice = ((x as u32 as u64) << 32) | y as u32 as u64;
+    SplitMix64::mix(seed ^ SplitMix64::mix(lattice))
+}
+
+/// uniform in [0, 1)
+fn hash_unit(x: i32, y: i32, seed: u64) -> f32 {
+    (hash(x, y, seed) >> 40) as f32 / (1_u64 << 24) as f32
+}
+
+fn quintic(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// one of 8 unit gradients dotted with (dx, dy)
+fn gradient(hash: u64, dx: f32, dy: f32) -> f32 {
+    const D: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const GRADIENTS: [(f32, f32); 8] = [
+        (1.0, 0.0),
+        (-1.0, 0.0),
+        (0.0, 1.0),
+        (0.0, -1.0),
+        (D, D),
+        (-D, D),
+        (D, -D),
+        (-D, -D),
+    ];
+    let (gx, gy) = GRADIENTS[(hash & 7) as usize];
+    gx * dx + gy * dy
+}
+
+impl NoiseBasis {
+    pub fn sample(self, x: f32, y: f32, seed: u64) -> f32 {
+        match self {
+            NoiseBasis::Value => Self::value(x, y, seed),
+            NoiseBasis::Perlin => Self::perlin(x, y, seed),
+            NoiseBasis::Simplex => Self::simplex(x, y, seed),
+            NoiseBasis::Worley => Self::cellular(x, y, seed).0,
+            NoiseBasis::Voronoi => Self::cellular(x, y, seed).1,
+        }
+    }
+
+    fn value(x: f32, y: f32, seed: u64) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (quintic(x - x0), quintic(y - y0));
+        let (ix, iy) = (x0 as i32, y0 as i32);
+
+        let corner = |dx: i32, dy: i32| hash_unit(ix + dx, iy + dy, seed) * 2.0 - 1.0;
+        lerp(
+            lerp(corner(0, 0), corner(1, 0), tx),
+            lerp(corner(0, 1), corner(1, 1), tx),
+            ty,
+        )
+    }
+
+    fn perlin(x: f32, y: f32, seed: u64) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (ix, iy) = (x0 as i32, y0 as i32);
+
+        let corner = |dx: i32, dy: i32| {
+            gradient(hash(ix + dx, iy + dy, seed), fx - dx as f32, fy - dy as f32)
+        };
+        let (tx, ty) = (quintic(fx), quintic(fy));
+        // unit gradients peak at sqrt(1/2)
+        std::f32::consts::SQRT_2
+            * lerp(
+                lerp(corner(0, 0), corner(1, 0), tx),
+                lerp(corner(0, 1), corner(1, 1), tx),
+                ty,
+            )
+    }
+
+    fn simplex(x: f32, y: f32, seed: u64) -> f32 {
+        let skew = (x + y) * SIMPLEX_F2;
+        let (i, j) = ((x + skew).floor(), (y + skew).floor());
+        let unskew = (i + j) * SIMPLEX_G2;
+        let (x0, y0) = (x - (i - unskew), y - (j - unskew));
+
+        // which of the two triangles of the skewed cell we are in
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (
+                i1,
+                j1,
+                x0 - i1 as f32 + SIMPLEX_G2,
+                y0 - j1 as f32 + SIMPLEX_G2,
+            ),
+            (
+                1,
+                1,
+                x0 - 1.0 + 2.0 * SIMPLEX_G2,
+                y0 - 1.0 + 2.0 * SIMPLEX_G2,
+            ),
+        ];
+
+        let (i, j) = (i as i32, j as i32);
+        let total: f32 = corners
+            .iter()
+            .map(|&(di, dj, dx, dy)| {
+                let t = 0.5 - dx * dx - dy * dy;
+                if t <= 0.0 {
+                    0.0
+                } else {
+                    t.powi(4) * gradient(hash(i + di, j + dj, seed), dx, dy)
+                }
+            })
+            .sum();
+
+        // Gustavson's 70 assumes gradients of length sqrt(2), ours are unit length
+        70.0 * std::f32::consts::SQRT_2 * total
+    }
+
+    /// (distance to the nearest feature point, value of its cell)
+    fn cellular(x: f32, y: f32, seed: u64) -> (f32, f32) {
+        let (ix, iy) = (x.floor() as i32, y.floor() as i32);
+
+        let mut nearest = (f32::MAX, 0.0);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (cx, cy) = (ix + dx, iy + dy);
+                let h = hash(cx, cy, seed);
+                // one jittered feature point per lattice cell
+                let px = cx as f32 + (h >> 40) as f32 / (1_u64 << 24) as f32;
+                let py = cy as f32 + ((h >> 16) & 0xFF_FFFF) as f32 / (1_u64 << 24) as f32;
+                let distance = ((px - x).powi(2) + (py - y).powi(2)).sqrt();
+                if distance < nearest.0 {
+                    nearest = (distance, (h & 0xFFFF) as f32 / 32767.5 - 1.0);
+                }
+            }
+        }
+        nearest
+    }
+}
+
+impl Fractal {
+    fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.octaves == 0 {
+            return Err("Fractal noise needs at least one octave".into());
+        }
+        if !(self.lacunarity.is_finite() && self.lacunarity > 0.0) {
+            return Err(format!("Invalid lacunarity: {}", self.lacunarity).into());
+        }
+        if !(self.gain.is_finite() && self.gain > 0.0) {
+            return Err(format!("Invalid gain: {}", self.gain).into());
+        }
+        Ok(())
+    }
+}
+
+fn check_frequency(frequency: f32) -> Result<(), Box<dyn std::error::Error>> {
+    if frequency.is_finite() && frequency > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("Invalid noise frequency: {}", frequency).into())
+    }
+}
+
+impl Field {
+    /// Fills a new `width` x `height` field with `f(x, y)`, rows in parallel.
+    pub fn from_fn<F>(width: usize, height: usize, f: F) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: Fn(usize, usize) -> f32 + Sync,
+    {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid field size: {}x{}", width, height).into());
+        }
+
+        let mut values = vec![0_f32; width * height].into_boxed_slice();
+        values
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, value) in row.iter_mut().enumerate() {
+                    *value = f(x, y);
+                }
+            });
+
+        Ok(Self {
+            flattened_field: values,
+            width,
+        })
+    }
+
+    /// One octave of `basis`. `frequency` is in lattice cells per field cell, so 1/64
+    /// gives features about 64 cells apart.
+    pub fn noise(
+        width: usize,
+        height: usize,
+        basis: NoiseBasis,
+        frequency: f32,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        check_frequency(frequency)?;
+        Self::from_fn(width, height, |x, y| {
+            basis.sample(x as f32 * frequency, y as f32 * frequency, seed)
+        })
+    }
+
+    /// Fractional Brownian motion: octaves of `basis` summed with falling amplitude and
+    /// divided by the total amplitude, so the range matches the basis.
+    pub fn fbm(
+        width: usize,
+        height: usize,
+        basis: NoiseBasis,
+        frequency: f32,
+        fractal: &Fractal,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        check_frequency(frequency)?;
+        fractal.check()?;
+
+        Self::from_fn(width, height, |x, y| {
+            let (mut f, mut amplitude) = (frequency, 1.0);
+            let (mut sum, mut total) = (0.0, 0.0);
+            for octave in 0..fractal.octaves {
+                let octave_seed = seed.wrapping_add(octave as u64);
+                sum += amplitude * basis.sample(x as f32 * f, y as f32 * f, octave_seed);
+                total += amplitude;
+                f *= fractal.lacunarity;
+                amplitude *= fractal.gain;
+            }
+            sum / total
+        })
+    }
+
+    /// Musgrave's ridged multifractal: sharp crests where the basis crosses zero, with
+    /// detail concentrated on the ridges. Roughly [0, 1].
+    pub fn ridged_multifractal(
+        width: usize,
+        height: usize,
+        basis: NoiseBasis,
+        frequency: f32,
+        fractal: &Fractal,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        check_frequency(frequency)?;
+        fractal.check()?;
+
+        Self::from_fn(width, height, |x, y| {
+            let (mut f, mut amplitude) = (frequency, 1.0);
+            let (mut sum, mut total) = (0.0, 0.0);
+            let mut weight = 1.0_f32;
+            for octave in 0..fractal.octaves {
+                let octave_seed = seed.wrapping_add(octave as u64);
+                let n = basis.sample(x as f32 * f, y as f32 * f, octave_seed);
+                let signal = (RIDGE_OFFSET - n.abs()).powi(2) * weight;
+                weight = (signal * RIDGE_GAIN).clamp(0.0, 1.0);
+
+                sum += amplitude * signal;
+                total += amplitude;
+                f *= fractal.lacunarity;
+                amplitude *= fractal.gain;
+            }
+            sum / total
+        })
+    }
+
+    /// Resamples the field at positions pushed around by two independent `basis` noises,
+    /// up to `amount` cells in each axis. Warping fBm with fBm gives the folded look.
+    pub fn domain_warp(
+        &self,
+        basis: NoiseBasis,
+        frequency: f32,
+        amount: f32,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        check_frequency(frequency)?;
+        if !amount.is_finite() {
+            return Err(format!("Invalid warp amount: {}", amount).into());
+        }
+
+        // second axis gets its own, unrelated seed
+        let seed_y = SplitMix64::mix(seed ^ 0x5EED);
+        Self::from_fn(self.width, self.height(), |x, y| {
+            let (fx, fy) = (x as f32 * frequency, y as f32 * frequency);
+            let dx = amount * basis.sample(fx, fy, seed);
+            let dy = amount * basis.sample(fx, fy, seed_y);
+            self.sample_bilinear(x as f32 + dx, y as f32 + dy)
+        })
+    }
+
+    /// Classic diamond-square midpoint displacement on the smallest 2^n + 1 grid covering
+    /// the requested size, cropped to it. `roughness` scales the displacement from one level
+    /// to the next, 0.5 is a natural looking default. Not normalized: corners start in
+    /// [-1, 1] and both steps of a level may displace, so for a roughness r below 1 values
+    /// are bounded by ±(1 + r) / (1 - r), which is ±3 at r = 0.5. Typical output at 0.5
+    /// stays within ±1.3; use `normalize` for a fixed range.
+    pub fn diamond_square(
+        width: usize,
+        height: usize,
+        roughness: f32,
+        seed: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid field size: {}x{}", width, height).into());
+        }
+        if !(roughness.is_finite() && roughness > 0.0) {
+            return Err(format!("Invalid roughness: {}", roughness).into());
+        }
+
+        let size = (width.max(height) - 1).max(1).next_power_of_two() + 1;
+        let mut grid = vec![0.0_f32; size * size];
+        // displacement depends only on the vertex, never on evaluation order
+        let jitter = |x: usize, y: usize| hash_unit(x as i32, y as i32, seed) * 2.0 - 1.0;
+
+        for &(x, y) in &[(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1)] {
+            grid[y * size + x] = jitter(x, y);
+        }
+
+        let mut step = size - 1;
+        let mut scale = roughness;
+        while step > 1 {
+            let half = step / 2;
+
+            // diamond step: centres of the squares
+            let centres: Vec<(usize, f32)> = (0..(size - 1) / step * ((size - 1) / step))
+                .into_par_iter()
+                .map(|k| {
+                    let x = (k % ((size - 1) / step)) * step + half;
+                    let y = (k / ((size - 1) / step)) * step + half;
+                    let mean = (grid[(y - half) * size + x - half]
+                        + grid[(y - half) * size + x + half]
+                        + grid[(y + half) * size + x - half]
+                        + grid[(y + half) * size + x + half])
+                        / 4.0;
+                    (y * size + x, mean + jitter(x, y) * scale)
+                })
+                .collect();
+            for (i, v) in centres {
+                grid[i] = v;
+            }
+
+            // square step: edge midpoints, averaging whichever of the 4 neighbours exist
+            let edges: Vec<(usize, f32)> = (0..size)
+                .into_par_iter()
+                .step_by(half)
+                .flat_map_iter(|y| {
+                    let start = if (y / half).is_multiple_of(2) { half } else { 0 };
+                    let grid = &grid;
+                    (start..size).step_by(step).map(move |x| {
+                        let mut sum = 0.0;
+                        let mut count = 0.0;
+                        if y >= half {
+                            sum += grid[(y - half) * size + x];
+                            count += 1.0;
+                        }
+                        if y + half < size {
+                            sum += grid[(y + half) * size + x];
+                            count += 1.0;
+                        }
+                        if x >= half {
+                            sum += grid[y * size + x - half];
+                            count += 1.0;
+                        }
+                        if x + half < size {
+                            sum += grid[y * size + x + half];
+                            count += 1.0;
+                        }
+                        (y * size + x, sum / count + jitter(x, y) * scale)
+                    })
+                })
+                .collect();
+            for (i, v) in edges {
+                grid[i] = v;
+            }
+
+            step = half;
+            scale *= roughness;
+        }
+
+        Self::from_fn(width, height, |x, y| grid[y * size + x])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASES: [NoiseBasis; 5] = [
+        NoiseBasis::Value,
+        NoiseBasis::Perlin,
+        NoiseBasis::Simplex,
+        NoiseBasis::Worley,
+        NoiseBasis::Voronoi,
+    ];
+
+    #[test]
+    fn same_seed_same_field() {
+        let fractal = Fractal::default();
+        for basis in BASES {
+            let generate = |seed| Field::fbm(24, 16, basis, 0.1, &fractal, seed).unwrap();
+            let (a, b, other) = (generate(7), generate(7), generate(8));
+            assert_eq!(a.flattened_field, b.flattened_field, "{:?}", basis);
+            assert_ne!(a.flattened_field, other.flattened_field, "{:?}", basis);
+        }
+
+        let ridged = |seed| {
+            Field::ridged_multifractal(16, 16, NoiseBasis::Perlin, 0.1, &fractal, seed).unwrap()
+        };
+        assert_eq!(ridged(3).flattened_field, ridged(3).flattened_field);
+
+        let diamond = |seed| Field::diamond_square(20, 13, 0.5, seed).unwrap();
+        assert_eq!(diamond(3).flattened_field, diamond(3).flattened_field);
+        assert_ne!(diamond(3).flattened_field, diamond(4).flattened_field);
+    }
+
+    #[test]
+    fn warp_is_deterministic() {
+        let base = Field::noise(16, 16, NoiseBasis::Simplex, 0.2, 1).unwrap();
+        let warp = |seed| {
+            base.domain_warp(NoiseBasis::Perlin, 0.1, 3.0, seed)
+                .unwrap()
+        };
+        assert_eq!(warp(5).flattened_field, warp(5).flattened_field);
+        assert!(base
+            .domain_warp(NoiseBasis::Perlin, 0.1, f32::NAN, 5)
+            .is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalKernel {
+    Sobel,
+    Scharr,
+    CentralDifference,
+}
+
+impl NormalKernel {
+    /// Smoothing weights across the derivative direction. Each kernel is the difference
+    /// of these three rows (or columns) two cells apart, so every one measures height per cell.
+    fn weights(self) -> [f32; 3] {
+        match self {
+            NormalKernel::Sobel => [1.0, 2.0, 1.0],
+            NormalKernel::Scharr => [3.0, 10.0, 3.0],
+            NormalKernel::CentralDifference => [0.0, 1.0, 0.0],
+        }
+    }
+}
+
+/// Which way the green channel points. OpenGL (and Blender, Unity) use +Y up the image,
+/// DirectX (and Unreal) use +Y down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalConvention {
+    OpenGl,
+    DirectX,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NormalMapOptions {
+    pub kernel: NormalKernel,
+    /// multiplier on the height gradient, higher values give more pronounced relief
+    pub strength: f32,
+    pub convention: NormalConvention,
+}
+
+impl Default for NormalMapOptions {
+    fn default() -> Self {
+        Self {
+            kernel: NormalKernel::Sobel,
+            strength: 1.0,
+            convention: NormalConvention::OpenGl,
+        }
+    }
+}
+
+/// Unit tangent-space normals, one per cell.
+pub struct NormalMap {
+    pub normals: Box<[[f32; 3]]>,
+    pub width: usize,
+}
+
+impl NormalMap {
+    pub fn height(&self) -> usize {
+        self.normals.len() / self.width
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [f32; 3] {
+        self.normals[y * self.width + x]
+    }
+
+    /// component in [-1, 1] mapped onto 0..=max
+    fn encode(c: f32, max: f32) -> f32 {
+        ((c * 0.5 + 0.5) * max).round()
+    }
+
+    /// 8 bit per channel RGB
+    pub fn write_png_u8(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let img = ImageBuffer::from_fn(self.width as u32, self.height() as u32, |x, y| {
+            let n = self.get(x as usize, y as usize);
+            Rgb(n.map(|c| Self::encode(c, u8::MAX as f32) as u8))
+        });
+
+        img.save(path)?;
+        Ok(())
+    }
+
+    /// 16 bit per channel RGB, for bakes where 8 bit banding shows on gentle slopes
+    pub fn write_png_u16(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let img = ImageBuffer::from_fn(self.width as u32, self.height() as u32, |x, y| {
+            let n = self.get(x as usize, y as usize);
+            Rgb(n.map(|c| Self::encode(c, u16::MAX as f32) as u16))
+        });
+
+        img.save(path)?;
+        Ok(())
+    }
+}
+
+impl Field {
+    /// Tangent-space normal map of the field seen from above, with edges clamped.
+    pub fn normal_map(
+        &self,
+        options: &NormalMapOptions,
+    ) -> Result<NormalMap, Box<dyn std::error::Error>> {
+        if !options.strength.is_finite() || options.strength < 0.0 {
+            return Err(format!("Invalid normal map strength: {}", options.strength).into());
+        }
+
+        let weights = options.kernel.weights();
+        let scale = options.strength / (2.0 * weights.iter().sum::<f32>());
+        // image rows run down, OpenGL's +Y runs up the image
+        let green = match options.convention {
+            NormalConvention::OpenGl => 1.0,
+            NormalConvention::DirectX => -1.0,
+        };
+
+        let mut normals = vec![[0.0_f32; 3]; self.flattened_field.len()].into_boxed_slice();
+        normals
+            .par_chunks_mut(self.width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let y = y as isize;
+                for (x, normal) in row.iter_mut().enumerate() {
+                    let x = x as isize;
+
+                    let mut dx = 0.0;
+                    let mut dy = 0.0;
+                    for (k, w) in weights.iter().enumerate() {
+                        let o = k as isize - 1;
+                        dx += w * (self.sample(x + 1, y + o) - self.sample(x - 1, y + o));
+                        dy += w * (self.sample(x + o, y + 1) - self.sample(x + o, y - 1));
+                    }
+
+                    let n = [-dx * scale, green * dy * scale, 1.0];
+                    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                    *normal = n.map(|c| c / length);
+                }
+            });
+
+        Ok(NormalMap {
+            normals,
+            width: self.width,
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use std::ops;
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    pub fn test(self, a: f32, b: f32) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterEqual => a >= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+        }
+    }
+}
+
+impl Field {
+    pub(crate) fn check_same_size(&self, other: &Field) -> Result<(), Box<dyn std::error::Error>> {
+        if self.width != other.width || self.flattened_field.len() != other.flattened_field.len() {
+            return Err(format!(
+                "Field size mismatch: {}x{} vs {}x{}",
+                self.width,
+                self.height(),
+                other.width,
+                other.height()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn map<F>(&self, f: F) -> Self
+    where
+        F: Fn(f32) -> f32 + Sync,
+    {
+        let result: Vec<f32> = self.flattened_field.par_iter().map(|&v| f(v)).collect();
+
+        Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+
+    pub fn zip_with<F>(&self, other: &Field, f: F) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: Fn(f32, f32) -> f32 + Sync,
+    {
+        self.check_same_size(other)?;
+
+        let result: Vec<f32> = self
+            .flattened_field
+            .par_iter()
+            .zip(other.flattened_field.par_iter())
+            .map(|(&a, &b)| f(a, b))
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    pub fn clamp(&self, min: f32, max: f32) -> Self {
+        self.map(|v| v.clamp(min, max))
+    }
+
+    /// 1.0 where `value <op> threshold` holds, 0.0 elsewhere.
+    pub fn mask(&self, op: Comparison, threshold: f32) -> Self {
+        self.map(|v| op.test(v, threshold) as u8 as f32)
+    }
+
+    /// 1.0 where `self <op> other` holds cell by cell, 0.0 elsewhere.
+    pub fn mask_with(
+        &self,
+        op: Comparison,
+        other: &Field,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        self.zip_with(other, |a, b| op.test(a, b) as u8 as f32)
+    }
+
+    /// `if_true` where `mask` is non-zero, `if_false` elsewhere.
+    pub fn select(
+        mask: &Field,
+        if_true: &Field,
+        if_false: &Field,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        mask.check_same_size(if_true)?;
+        mask.check_same_size(if_false)?;
+
+        let result: Vec<f32> = mask
+            .flattened_field
+            .par_iter()
+            .zip(if_true.flattened_field.par_iter())
+            .zip(if_false.flattened_field.par_iter())
+            .map(|((&m, &a), &b)| if m != 0.0 { a } else { b })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: mask.width,
+        })
+    }
+}
+
+// operators panic on mismatched sizes, use `zip_with` to get an error instead
+macro_rules! elementwise_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl ops::$trait<&Field> for &Field {
+            type Output = Field;
+
+            fn $method(self, rhs: &Field) -> Field {
+                self.zip_with(rhs, |a, b| a $op b)
+                    .expect(concat!("cannot ", stringify!($method), " fields of different sizes"))
+            }
+        }
+
+        impl ops::$trait<Field> for Field {
+            type Output = Field;
+
+            fn $method(self, rhs: Field) -> Field {
+                &self $op &rhs
+            }
+        }
+
+        impl ops::$trait<&Field> for Field {
+            type Output = Field;
+
+            fn $method(self, rhs: &Field) -> Field {
+                &self $op rhs
+            }
+        }
+
+        impl ops::$trait<f32> for &Field {
+            type Output = Field;
+
+            fn $method(self, rhs: f32) -> Field {
+                self.map(|v| v $op rhs)
+            }
+        }
+
+        impl ops::$trait<f32> for Field {
+            type Output = Field;
+
+            fn $method(self, rhs: f32) -> Field {
+                &self $op rhs
+            }
+        }
+
+        impl ops::$trait<&Field> for f32 {
+            type Output = Field;
+
+            fn $method(self, rhs: &Field) -> Field {
+                rhs.map(|v| self $op v)
+            }
+        }
+    };
+}
+
+elementwise_op!(Add, add, +);
+elementwise_op!(Sub, sub, -);
+elementwise_op!(Mul, mul, *);
+elementwise_op!(Div, div, /);
+
+impl ops::Neg for &Field {
+    type Output = Field;
+
+    fn neg(self) -> Field {
+        self.map(|v| -v)
+    }
+}
@@ -0,0 +1,144 @@
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+// 5-tap binomial approximation of a Gaussian (Burt & Adelson)
+const BINOMIAL_5: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+impl Field {
+    /// Blurs with the 5-tap binomial kernel and keeps every other cell, starting at 0.
+    ///
+    /// Sizes of the form 2^n + 1 keep their corners: 513 -> 257 -> 129.
+    pub fn gaussian_reduce(&self) -> Result<Self, Box<dyn std::error::Error>> {
+        let width = self.width.div_ceil(2);
+        let height = self.height().div_ceil(2);
+        if self.width < 2 && self.height() < 2 {
+            return Err("Field is too small to reduce".into());
+        }
+
+        // horizontal blur on the kept columns only, then vertical on the kept rows
+        let mut rows = vec![0.0_f32; width * self.height()];
+        rows.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                *out = BINOMIAL_5
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| w * self.sample(2 * x as isize + k as isize - 2, y as isize))
+                    .sum();
+            }
+        });
+        let blurred_rows = Self {
+            flattened_field: rows.into_boxed_slice(),
+            width,
+        };
+
+        let mut result = vec![0.0_f32; width * height];
+        result
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    *out = BINOMIAL_5
+                        .iter()
+                        .enumerate()
+                        .map(|(k, w)| {
+                            w * blurred_rows.sample(x as isize, 2 * y as isize + k as isize - 2)
+                        })
+                        .sum();
+                }
+            });
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width,
+        })
+    }
+
+    /// Averages 2x2 blocks; odd trailing rows/columns average whatever cells they have.
+    pub fn mean_reduce(&self) -> Result<Self, Box<dyn std::error::Error>> {
+        let width = self.width.div_ceil(2);
+        let height = self.height().div_ceil(2);
+        if self.width < 2 && self.height() < 2 {
+            return Err("Field is too small to reduce".into());
+        }
+
+        let mut result = vec![0.0_f32; width * height];
+        result
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    let mut sum = 0.0;
+                    let mut count = 0;
+                    for sy in 2 * y..(2 * y + 2).min(self.height()) {
+                        for sx in 2 * x..(2 * x + 2).min(self.width) {
+                            sum += self.get(sx, sy);
+                            count += 1;
+                        }
+                    }
+                    *out = sum / count as f32;
+                }
+            });
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width,
+        })
+    }
+
+    /// `levels` fields, the first being a copy of this one and each next one a Gaussian reduction.
+    pub fn gaussian_pyramid(&self, levels: usize) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        self.pyramid(levels, Self::gaussian_reduce)
+    }
+
+    /// `levels` fields, the first being a copy of this one and each next one a 2x2 mean reduction.
+    pub fn mean_pyramid(&self, levels: usize) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        self.pyramid(levels, Self::mean_reduce)
+    }
+
+    fn pyramid<F>(&self, levels: usize, reduce: F) -> Result<Vec<Self>, Box<dyn std::error::Error>>
+    where
+        F: Fn(&Self) -> Result<Self, Box<dyn std::error::Error>>,
+    {
+        if levels == 0 {
+            return Err("A pyramid needs at least one level".into());
+        }
+
+        let mut pyramid = vec![self.clone()];
+        while pyramid.len() < levels {
+            let next = reduce(&pyramid[pyramid.len() - 1])?;
+            pyramid.push(next);
+        }
+
+        Ok(pyramid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_keep_the_input_and_halve() {
+        let field = Field::from_fn(17, 9, |x, y| (x * 3 + y) as f32).unwrap();
+        for pyramid in [
+            field.gaussian_pyramid(4).unwrap(),
+            field.mean_pyramid(4).unwrap(),
+        ] {
+            assert_eq!(pyramid[0].flattened_field, field.flattened_field);
+            let sizes: Vec<_> = pyramid.iter().map(|l| (l.width, l.height())).collect();
+            assert_eq!(sizes, [(17, 9), (9, 5), (5, 3), (3, 2)]);
+        }
+        assert!(field.gaussian_pyramid(0).is_err());
+    }
+
+    #[test]
+    fn constant_field_stays_constant() {
+        let field = Field::from_fn(10, 7, |_, _| 4.5).unwrap();
+        let gaussian = field.gaussian_reduce().unwrap();
+        let mean = field.mean_reduce().unwrap();
+        assert!(gaussian.values().all(|v| (v - 4.5).abs() < 1e-6));
+        assert!(mean.values().all(|v| v == 4.5));
+    }
+}
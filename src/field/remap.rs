@@ -0,0 +1,269 @@
+use crate::field::field::Field;
+use crate::field::noise::NoiseBasis;
+use crate::field::view::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveInterpolation {
+    Linear,
+    /// monotone cubic (Fritsch-Carlson), smooth without overshooting the control points
+    Spline,
+}
+
+/// Transfer curve through `(input, output)` control points. Inputs past either end take
+/// the end value.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+    /// spline tangents, one per point
+    tangents: Vec<f32>,
+    interpolation: CurveInterpolation,
+}
+
+impl Curve {
+    pub fn new(
+        points: &[(f32, f32)],
+        interpolation: CurveInterpolation,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if points.len() < 2 {
+            return Err("A curve needs at least two points".into());
+        }
+        if points.iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) {
+            return Err("Curve points must be finite".into());
+        }
+        if points.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err("Curve inputs must be strictly increasing".into());
+        }
+
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect();
+
+        let mut tangents = vec![0.0; points.len()];
+        tangents[0] = secants[0];
+        tangents[points.len() - 1] = secants[secants.len() - 1];
+        for i in 1..points.len() - 1 {
+            // flat at local extrema, otherwise the mean of the two secants
+            tangents[i] = if secants[i - 1] * secants[i] <= 0.0 {
+                0.0
+            } else {
+                (secants[i - 1] + secants[i]) / 2.0
+            };
+        }
+        // Fritsch-Carlson limit keeps every segment monotone
+        for (i, &secant) in secants.iter().enumerate() {
+            if secant == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let a = tangents[i] / secant;
+            let b = tangents[i + 1] / secant;
+            let length = (a * a + b * b).sqrt();
+            if length > 3.0 {
+                tangents[i] = 3.0 * a / length * secant;
+                tangents[i + 1] = 3.0 * b / length * secant;
+            }
+        }
+
+        Ok(Self {
+            points: points.to_vec(),
+            tangents,
+            interpolation,
+        })
+    }
+
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let last = self.points.len() - 1;
+        if x <= self.points[0].0 {
+            return self.points[0].1;
+        }
+        if x >= self.points[last].0 {
+            return self.points[last].1;
+        }
+
+        let i = self.points.partition_point(|p| p.0 <= x) - 1;
+        let (x0, y0) = self.points[i];
+        let (x1, y1) = self.points[i + 1];
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+
+        match self.interpolation {
+            CurveInterpolation::Linear => y0 + (y1 - y0) * t,
+            CurveInterpolation::Spline => {
+                let t2 = t * t;
+                let t3 = t2 * t;
+                (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                    + (t3 - 2.0 * t2 + t) * h * self.tangents[i]
+                    + (-2.0 * t3 + 3.0 * t2) * y1
+                    + (t3 - t2) * h * self.tangents[i + 1]
+            }
+        }
+    }
+}
+
+/// Terrace shaping for `Field::terrace`.
+#[derive(Debug, Clone, Copy)]
+pub struct Terrace {
+    pub steps: usize,
+    /// 0 leaves the slope untouched, towards 1 the treads flatten and the risers steepen
+    pub sharpness: f32,
+    /// how far noise moves the risers up or down, in steps
+    pub jitter: f32,
+    /// noise frequency of the jitter, in lattice cells per field cell
+    pub jitter_frequency: f32,
+    pub seed: u64,
+}
+
+impl Default for Terrace {
+    fn default() -> Self {
+        Self {
+            steps: 8,
+            sharpness: 0.8,
+            jitter: 0.0,
+            jitter_frequency: 1.0 / 32.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Terrace {
+    fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.steps == 0 {
+            return Err("Terracing needs at least one step".into());
+        }
+        if self.sharpness.is_nan() || !(0.0..1.0).contains(&self.sharpness) {
+            return Err(format!("Invalid terrace sharpness: {}", self.sharpness).into());
+        }
+        if !self.jitter.is_finite() {
+            return Err(format!("Invalid terrace jitter: {}", self.jitter).into());
+        }
+        if !(self.jitter_frequency.is_finite() && self.jitter_frequency > 0.0) {
+            return Err(format!("Invalid jitter frequency: {}", self.jitter_frequency).into());
+        }
+        Ok(())
+    }
+
+    /// exponent applied to the position within a step
+    fn exponent(&self) -> f32 {
+        1.0 / (1.0 - self.sharpness)
+    }
+
+    fn offset(&self, x: usize, y: usize) -> f32 {
+        if self.jitter == 0.0 {
+            return 0.0;
+        }
+        let f = self.jitter_frequency;
+        self.jitter * NoiseBasis::Simplex.sample(x as f32 * f, y as f32 * f, self.seed)
+    }
+}
+
+impl Field {
+    /// Passes every value through `curve`.
+    pub fn remap(&self, curve: &Curve) -> Self {
+        self.map(|v| curve.evaluate(v))
+    }
+
+    /// Like `remap`, but the curve works on heights rescaled to [0, 1] and the result is
+    /// scaled back to the original range.
+    pub fn remap_relative(&self, curve: &Curve) -> Result<Self, Box<dyn std::error::Error>> {
+        let (min, max) = self.value_range()?;
+        let range = max - min;
+        Ok(self.map(|v| min + curve.evaluate((v - min) / range) * range))
+    }
+
+    fn value_range(&self) -> Result<(f32, f32), Box<dyn std::error::Error>> {
+        let stats = self.stats();
+        if stats.count == 0 || stats.max - stats.min <= f32::EPSILON {
+            return Err("Field has no height range to reshape".into());
+        }
+        Ok((stats.min, stats.max))
+    }
+
+    /// Cuts the height range into `steps` terraces, with jitter moving where the risers sit.
+    /// The shaping is invertible with `unterrace`.
+    pub fn terrace(&self, terrace: &Terrace) -> Result<Self, Box<dyn std::error::Error>> {
+        terrace.check()?;
+        let (min, max) = self.value_range()?;
+        let steps = terrace.steps as f32;
+        let exponent = terrace.exponent();
+
+        Self::from_fn(self.width, self.height(), |x, y| {
+            let offset = terrace.offset(x, y);
+            let s = (self.get(x, y) - min) / (max - min) * steps + offset;
+            let base = s.floor();
+            // the offset fades out as the treads flatten, so sharpness 0 is the identity
+            let shaped = base + (s - base).powf(exponent) - offset * (1.0 - terrace.sharpness);
+            min + shaped / steps * (max - min)
+        })
+    }
+
+    /// Inverse of `terrace` with the same settings, for a field whose range before terracing
+    /// was `min..=max`. Detail squeezed onto the flattest part of a tread is below f32
+    /// precision and comes back as a small ramp. Terraces in source data were made with
+    /// some other curve, so this undoes our own terracing only.
+    pub fn unterrace(
+        &self,
+        terrace: &Terrace,
+        min: f32,
+        max: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        terrace.check()?;
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            return Err(format!("Invalid height range: {}..{}", min, max).into());
+        }
+        let steps = terrace.steps as f32;
+        let exponent = terrace.exponent();
+
+        Self::from_fn(self.width, self.height(), |x, y| {
+            let offset = terrace.offset(x, y);
+            let shaped =
+                (self.get(x, y) - min) / (max - min) * steps + offset * (1.0 - terrace.sharpness);
+            let base = shaped.floor();
+            let s = base + (shaped - base).powf(1.0 / exponent) - offset;
+            min + s / steps * (max - min)
+        })
+    }
+
+    /// Flattens everything above `level` into a mesa top, rounding the rim over `softness`
+    /// height units with a polynomial smooth minimum.
+    pub fn plateau(&self, level: f32, softness: f32) -> Result<Self, Box<dyn std::error::Error>> {
+        if softness.is_nan() || softness < 0.0 {
+            return Err(format!("Invalid plateau softness: {}", softness).into());
+        }
+
+        Ok(self.map(|v| {
+            if softness == 0.0 {
+                return v.min(level);
+            }
+            let h = (0.5 + 0.5 * (level - v) / softness).clamp(0.0, 1.0);
+            level + (v - level) * h - softness * h * (1.0 - h)
+        }))
+    }
+
+    /// Drops everything below `level` by `depth`, leaving steep walls whose width in
+    /// height units is `softness`.
+    pub fn canyon(
+        &self,
+        level: f32,
+        depth: f32,
+        softness: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if softness.is_nan() || softness < 0.0 {
+            return Err(format!("Invalid canyon softness: {}", softness).into());
+        }
+        if !depth.is_finite() {
+            return Err(format!("Invalid canyon depth: {}", depth).into());
+        }
+
+        Ok(self.map(|v| {
+            let inside = if softness == 0.0 {
+                (v < level) as u8 as f32
+            } else {
+                let t = ((level + softness - v) / (2.0 * softness)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            };
+            v - depth * inside
+        }))
+    }
+}
@@ -0,0 +1,236 @@
+use std::f32::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+// coverage below this is treated as nodata, negative lobes can push the sum to ~0
+const MIN_COVERAGE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom cubic
+    Bicubic,
+    /// 3-lobed Lanczos
+    Lanczos,
+    /// mean of the covered source cells (box filter weighted by overlap)
+    Area,
+}
+
+impl ResampleFilter {
+    fn support(self) -> f32 {
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Area => 0.5,
+            ResampleFilter::Bilinear => 1.0,
+            ResampleFilter::Bicubic => 2.0,
+            ResampleFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, t: f32) -> f32 {
+        let t = t.abs();
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Area => (t < 0.5) as u8 as f32,
+            ResampleFilter::Bilinear => (1.0 - t).max(0.0),
+            ResampleFilter::Bicubic => {
+                const A: f32 = -0.5;
+                if t < 1.0 {
+                    ((A + 2.0) * t - (A + 3.0)) * t * t + 1.0
+                } else if t < 2.0 {
+                    ((A * t - 5.0 * A) * t + 8.0 * A) * t - 4.0 * A
+                } else {
+                    0.0
+                }
+            }
+            ResampleFilter::Lanczos => {
+                if t < 1e-6 {
+                    1.0
+                } else if t < 3.0 {
+                    let x = PI * t;
+                    3.0 * x.sin() * (x / 3.0).sin() / (x * x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Source taps for one output sample: first source index and one weight per tap.
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Per-axis filter taps mapping `src` cells onto `dst` cells with pixel centres aligned.
+/// When shrinking the kernel is stretched by the scale so every source cell contributes.
+fn axis_taps(src: usize, dst: usize, filter: ResampleFilter) -> Vec<Taps> {
+    let scale = src as f32 / dst as f32;
+
+    (0..dst)
+        .map(|o| {
+            if filter == ResampleFilter::Nearest {
+                let i = (((o as f32 + 0.5) * scale) as usize).min(src - 1);
+                return Taps {
+                    start: i,
+                    weights: vec![1.0],
+                };
+            }
+
+            if filter == ResampleFilter::Area {
+                let from = o as f32 * scale;
+                let to = from + scale;
+                let start = (from.floor() as usize).min(src - 1);
+                let end = (to.ceil() as usize).clamp(start + 1, src);
+                let weights = (start..end)
+                    .map(|i| (to.min(i as f32 + 1.0) - from.max(i as f32)).max(0.0))
+                    .collect();
+                return Taps { start, weights };
+            }
+
+            let filter_scale = scale.max(1.0);
+            let center = (o as f32 + 0.5) * scale - 0.5;
+            let support = filter.support() * filter_scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize + 1).min(src);
+
+            let weights = (start..end)
+                .map(|i| filter.weight((i as f32 - center) / filter_scale))
+                .collect();
+            Taps { start, weights }
+        })
+        .collect()
+}
+
+impl Field {
+    /// Float-preserving resample to `width` x `height`.
+    ///
+    /// Runs as two separable passes. NaN cells, and cells equal to `nodata` when given, are
+    /// left out of every weighted sum and the remaining weights renormalized; outputs with no
+    /// valid coverage are written as `nodata` (NaN when `None`).
+    pub fn resample(
+        &self,
+        width: usize,
+        height: usize,
+        filter: ResampleFilter,
+        nodata: Option<f32>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid resample size: {}x{}", width, height).into());
+        }
+        if self.flattened_field.is_empty() {
+            return Err("Cannot resample an empty field".into());
+        }
+
+        let src_width = self.width;
+        let src_height = self.height();
+        let is_valid = |v: f32| !v.is_nan() && nodata != Some(v);
+
+        let x_taps = axis_taps(src_width, width, filter);
+        let y_taps = axis_taps(src_height, height, filter);
+
+        // horizontal pass keeps the weighted sum and the weight of the valid taps separately,
+        // so the vertical pass can renormalize over exactly the valid 2D footprint
+        let mut sums = vec![0.0_f32; width * src_height];
+        let mut coverage = vec![0.0_f32; width * src_height];
+        sums.par_chunks_mut(width)
+            .zip(coverage.par_chunks_mut(width))
+            .enumerate()
+            .for_each(|(y, (sum_row, coverage_row))| {
+                let row = &self.flattened_field[y * src_width..(y + 1) * src_width];
+                for (x, taps) in x_taps.iter().enumerate() {
+                    let (mut sum, mut weight) = (0.0, 0.0);
+                    for (k, &w) in taps.weights.iter().enumerate() {
+                        let v = row[taps.start + k];
+                        if is_valid(v) {
+                            sum += w * v;
+                            weight += w;
+                        }
+                    }
+                    sum_row[x] = sum;
+                    coverage_row[x] = weight;
+                }
+            });
+
+        let x_totals: Vec<f32> = x_taps.iter().map(|t| t.weights.iter().sum()).collect();
+        let fill = nodata.unwrap_or(f32::NAN);
+        let mut result = vec![0.0_f32; width * height];
+        result
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, out_row)| {
+                let taps = &y_taps[y];
+                let total: f32 = taps.weights.iter().sum();
+                for (x, out) in out_row.iter_mut().enumerate() {
+                    let (mut sum, mut weight) = (0.0, 0.0);
+                    for (k, &w) in taps.weights.iter().enumerate() {
+                        let i = (taps.start + k) * width + x;
+                        sum += w * sums[i];
+                        weight += w * coverage[i];
+                    }
+                    *out = if weight.abs() > MIN_COVERAGE * total * x_totals[x] {
+                        sum / weight
+                    } else {
+                        fill
+                    };
+                }
+            });
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [ResampleFilter; 5] = [
+        ResampleFilter::Nearest,
+        ResampleFilter::Bilinear,
+        ResampleFilter::Bicubic,
+        ResampleFilter::Lanczos,
+        ResampleFilter::Area,
+    ];
+
+    fn terrain() -> Field {
+        Field::from_fn(11, 7, |x, y| {
+            (x as f32 * 0.7).sin() * 3.0 + (y * y) as f32 * 0.2
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn same_size_returns_the_input() {
+        let field = terrain();
+        for filter in FILTERS {
+            let resampled = field.resample(11, 7, filter, None).unwrap();
+            let error = resampled
+                .values()
+                .zip(field.values())
+                .fold(0.0_f32, |m, (a, b)| m.max((a - b).abs()));
+            assert!(error < 1e-5, "{:?} is off by {}", filter, error);
+        }
+    }
+
+    #[test]
+    fn nodata_stays_out_of_the_sums() {
+        let mut field = Field::from_fn(8, 8, |_, _| 2.0).unwrap();
+        field.flattened_field[3 * 8 + 3] = -9999.0;
+
+        for filter in FILTERS {
+            let resampled = field.resample(5, 3, filter, Some(-9999.0)).unwrap();
+            assert_eq!((resampled.width, resampled.height()), (5, 3));
+            assert!(
+                resampled.values().all(|v| (v - 2.0).abs() < 1e-5),
+                "{:?} mixed in nodata",
+                filter
+            );
+        }
+    }
+}
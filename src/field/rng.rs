@@ -0,0 +1,36 @@
+/// SplitMix64 (Steele et al. 2014). Small, fast and plenty for placing droplets or seeding
+/// noise, not for anything cryptographic.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Independent generator for item `index` of a run, so parallel work does not depend
+    /// on which thread picks up which item.
+    pub(crate) fn stream(seed: u64, index: u64) -> Self {
+        Self::new(Self::mix(seed.wrapping_add(Self::mix(index))))
+    }
+
+    pub(crate) fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(Self::GOLDEN_GAMMA);
+        Self::mix(self.state)
+    }
+
+    /// uniform in [0, 1)
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32
+    }
+}
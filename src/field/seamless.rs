@@ -0,0 +1,328 @@
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::field::fft::{transform_2d, Complex};
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+/// How reads past the edge of a field are resolved, see `Raster::boundary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Boundary {
+    /// repeat the nearest edge cell
+    #[default]
+    Clamp,
+    /// continue from the opposite edge, as if the field were tiled
+    Wrap,
+}
+
+/// A raster read with `Boundary::Wrap`, made by `Raster::wrapped`.
+///
+/// The gradient, curvature and slope kernels on it are periodic. Depression filling and
+/// the flow kernels built on it return an error instead.
+#[derive(Clone, Copy)]
+pub struct Wrapped<'a, R: Raster + ?Sized>(pub &'a R);
+
+impl<R: Raster + ?Sized> Raster for Wrapped<'_, R> {
+    fn width(&self) -> usize {
+        self.0.width()
+    }
+
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    fn row(&self, y: usize) -> &[f32] {
+        self.0.row(y)
+    }
+
+    fn boundary(&self) -> Boundary {
+        Boundary::Wrap
+    }
+}
+
+/// Discontinuity where opposite edges of a field meet when it is tiled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seam {
+    /// mean absolute height step across the seam
+    pub mean_step: f32,
+    pub max_step: f32,
+    /// mean absolute change in slope across the seam, a crease even where heights agree
+    pub mean_crease: f32,
+    /// `mean_step` over the mean step between neighbours inside the field, about 1 when the
+    /// seam is no rougher than the terrain around it
+    pub relative_step: f32,
+}
+
+/// Seams of a field tiled with copies of itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seams {
+    /// the right edge running into the left edge
+    pub left_right: Seam,
+    /// the bottom edge running into the top edge
+    pub top_bottom: Seam,
+}
+
+/// Seam between the last and first line across, with `at(i, j)` reading cell `j` across on
+/// line `i` along the seam.
+fn seam(along: usize, across: usize, at: impl Fn(usize, usize) -> f32 + Sync) -> Seam {
+    let (sum, max, crease, interior) = (0..along)
+        .into_par_iter()
+        .map(|i| {
+            let step = (at(i, 0) - at(i, across - 1)).abs();
+            let before = at(i, across - 1) - at(i, across - 2);
+            let after = at(i, 1) - at(i, 0);
+            let interior: f32 = (1..across).map(|j| (at(i, j) - at(i, j - 1)).abs()).sum();
+            (step, step, (after - before).abs(), interior)
+        })
+        .reduce(
+            || (0.0, 0.0, 0.0, 0.0),
+            |a, b| (a.0 + b.0, a.1.max(b.1), a.2 + b.2, a.3 + b.3),
+        );
+
+    let mean_step = sum / along as f32;
+    let mean_interior = interior / (along * (across - 1)) as f32;
+    let relative_step = if mean_interior > 0.0 {
+        mean_step / mean_interior
+    } else if mean_step > 0.0 {
+        f32::INFINITY
+    } else {
+        0.0
+    };
+
+    Seam {
+        mean_step,
+        max_step: max,
+        mean_crease: crease / along as f32,
+        relative_step,
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl Field {
+    pub fn sample_with(&self, x: isize, y: isize, boundary: Boundary) -> f32 {
+        match boundary {
+            Boundary::Clamp => self.sample(x, y),
+            Boundary::Wrap => self.wrapped().sample(x, y),
+        }
+    }
+
+    /// bilinear read at a fractional cell position
+    pub fn sample_bilinear_with(&self, x: f32, y: f32, boundary: Boundary) -> f32 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let at = |x, y| self.sample_with(x, y, boundary);
+
+        let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+        let bottom = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    /// Copy grown by `margin` cells on every side, filled according to `boundary`.
+    pub fn pad(&self, margin: usize, boundary: Boundary) -> Self {
+        let width = self.width + 2 * margin;
+        let m = margin as isize;
+        let values: Vec<f32> = (0..width * (self.height() + 2 * margin))
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                self.sample_with(x - m, y - m, boundary)
+            })
+            .collect();
+
+        Self {
+            flattened_field: values.into_boxed_slice(),
+            width,
+        }
+    }
+
+    /// Runs `kernel` with periodic boundaries: the field is wrapped `margin` cells onto
+    /// every side first and the margin cropped off the result.
+    ///
+    /// Only for local kernels that read no further than `margin` cells, such as normal maps,
+    /// openness or geomorphons within their radius; the kernels on `Raster` take `wrapped()`
+    /// instead. Global ones (depression filling, flow accumulation, distance transforms,
+    /// component labelling, viewsheds, erosion) see a bigger field with a fake edge and
+    /// give wrong results here, they have no periodic form in this crate.
+    pub fn apply_wrapped<F>(
+        &self,
+        margin: usize,
+        kernel: F,
+    ) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&Field) -> Result<Field, Box<dyn std::error::Error>>,
+    {
+        let padded = self.pad(margin, Boundary::Wrap);
+        let result = kernel(&padded)?;
+        if result.width != padded.width || result.height() != padded.height() {
+            return Err("Kernel changed the size of the field".into());
+        }
+
+        Ok(result
+            .window(margin, margin, self.width, self.height())?
+            .to_field())
+    }
+
+    /// Moves the field `dx` cells right and `dy` cells down, wrapping around. Rolling by
+    /// half the size brings the seams into the middle to inspect or paint over.
+    pub fn roll(&self, dx: isize, dy: isize) -> Self {
+        let width = self.width;
+        let values: Vec<f32> = (0..self.flattened_field.len())
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                self.sample_with(x - dx, y - dy, Boundary::Wrap)
+            })
+            .collect();
+
+        Self {
+            flattened_field: values.into_boxed_slice(),
+            width,
+        }
+    }
+
+    /// How visible the seams are when the field is tiled with copies of itself.
+    pub fn seams(&self) -> Result<Seams, Box<dyn std::error::Error>> {
+        let (width, height) = (self.width, self.height());
+        if width < 3 || height < 3 {
+            return Err("Seams need a field of at least 3x3".into());
+        }
+
+        Ok(Seams {
+            left_right: seam(height, width, |i, j| self.get(j, i)),
+            top_bottom: seam(width, height, |i, j| self.get(i, j)),
+        })
+    }
+
+    /// Tileable version made by cross-fading the last `overlap` rows and columns into the
+    /// first ones. The result is `overlap` cells smaller in each direction and keeps every
+    /// cell outside the blend untouched.
+    pub fn tileable_crossfade(&self, overlap: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = (self.width, self.height());
+        if overlap == 0 || 2 * overlap >= width.min(height) {
+            return Err(format!(
+                "Invalid overlap {} for a {}x{} field, it must be under half of each side",
+                overlap, width, height
+            )
+            .into());
+        }
+
+        let (tile_width, tile_height) = (width - overlap, height - overlap);
+        let weight = |i: usize| smoothstep((i as f32 + 0.5) / overlap as f32);
+        let columns = Self::from_fn(tile_width, height, |x, y| {
+            if x < overlap {
+                let t = weight(x);
+                self.get(x + tile_width, y) * (1.0 - t) + self.get(x, y) * t
+            } else {
+                self.get(x, y)
+            }
+        })?;
+        Self::from_fn(tile_width, tile_height, |x, y| {
+            if y < overlap {
+                let t = weight(y);
+                columns.get(x, y + tile_height) * (1.0 - t) + columns.get(x, y) * t
+            } else {
+                columns.get(x, y)
+            }
+        })
+    }
+
+    /// Tileable version that keeps the size and all the detail: the periodic part of the
+    /// periodic-plus-smooth decomposition (Moisan 2011). A smooth correction, found by
+    /// solving a Poisson equation whose sources sit on the seams, is subtracted so that
+    /// opposite edges meet, and the mean is unchanged.
+    pub fn tileable_poisson(&self) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = (self.width, self.height());
+        if width < 2 || height < 2 {
+            return Err("Seam blending needs a field of at least 2x2".into());
+        }
+
+        // mismatch across each seam, placed on the cells either side of it
+        let mut boundary = vec![Complex::default(); width * height];
+        for y in 0..height {
+            let jump = (self.get(width - 1, y) - self.get(0, y)) as f64;
+            boundary[y * width].re += jump;
+            boundary[y * width + width - 1].re -= jump;
+        }
+        for x in 0..width {
+            let jump = (self.get(x, height - 1) - self.get(x, 0)) as f64;
+            boundary[x].re += jump;
+            boundary[(height - 1) * width + x].re -= jump;
+        }
+
+        // inverse of the periodic discrete Laplacian, the smooth part has zero mean
+        let mut spectrum = transform_2d(&boundary, width, height, false);
+        spectrum.par_iter_mut().enumerate().for_each(|(i, c)| {
+            let (u, v) = ((i % width) as f64, (i / width) as f64);
+            let denominator = 2.0 * (2.0 * PI * u / width as f64).cos()
+                + 2.0 * (2.0 * PI * v / height as f64).cos()
+                - 4.0;
+            *c = if i == 0 {
+                Complex::default()
+            } else {
+                Complex::new(c.re / denominator, c.im / denominator)
+            };
+        });
+        let smooth = transform_2d(&spectrum, width, height, true);
+
+        Self::from_fn(width, height, |x, y| {
+            (self.get(x, y) as f64 - smooth[y * width + x].re) as f32
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a slope with ripples on it, far from periodic so every seam has a step
+    fn slope(width: usize, height: usize) -> Field {
+        Field::from_fn(width, height, |x, y| {
+            x as f32 * 0.3 + (y as f32 * 1.7).sin() - y as f32 * 0.1
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn wrapped_kernels_commute_with_roll() {
+        let field = slope(13, 10);
+        let rolled = field.roll(5, -3);
+
+        let sobel = field.wrapped().sobel().unwrap().roll(5, -3);
+        let rolled_sobel = rolled.wrapped().sobel().unwrap();
+        assert_eq!(sobel.flattened_field, rolled_sobel.flattened_field);
+
+        let (crests, ..) = field.wrapped().structural_lines().unwrap();
+        let (rolled_crests, ..) = rolled.wrapped().structural_lines().unwrap();
+        assert_eq!(
+            crests.roll(5, -3).flattened_field,
+            rolled_crests.flattened_field
+        );
+
+        let slope = field.wrapped().slope(1.0).unwrap().roll(5, -3);
+        let rolled_slope = rolled.wrapped().slope(1.0).unwrap();
+        assert_eq!(slope.flattened_field, rolled_slope.flattened_field);
+    }
+
+    #[test]
+    fn wrapped_matches_padded_kernel() {
+        let field = slope(11, 7);
+        let padded = field.apply_wrapped(1, |f| f.prewitt()).unwrap();
+        let wrapped = field.wrapped().prewitt().unwrap();
+        assert_eq!(padded.flattened_field, wrapped.flattened_field);
+    }
+
+    #[test]
+    fn flow_kernels_reject_wrapped() {
+        let field = slope(8, 8);
+        assert!(field.wrapped().fill_depressions().is_err());
+        assert!(field.wrapped().flow_accumulation().is_err());
+        assert!(field.fill_depressions().is_ok());
+    }
+}
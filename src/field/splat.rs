@@ -0,0 +1,279 @@
+use std::path::{Path, PathBuf};
+
+use image::{ImageBuffer, Rgba};
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+/// Accepted band of an input. Inside `min..=max` the weight is 1, outside it fades to 0
+/// over `falloff` units with a smoothstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplatRange {
+    pub min: f32,
+    pub max: f32,
+    pub falloff: f32,
+}
+
+impl SplatRange {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            falloff: 0.0,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    fn check(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.min.is_nan() || self.max.is_nan() || self.min > self.max {
+            return Err(format!("Invalid {} range: {}..{}", name, self.min, self.max).into());
+        }
+        if self.falloff.is_nan() || self.falloff < 0.0 {
+            return Err(format!("Invalid {} falloff: {}", name, self.falloff).into());
+        }
+        Ok(())
+    }
+
+    pub fn weight(&self, value: f32) -> f32 {
+        let distance = if value < self.min {
+            self.min - value
+        } else if value > self.max {
+            value - self.max
+        } else {
+            return 1.0;
+        };
+
+        if distance >= self.falloff {
+            return 0.0;
+        }
+        let t = 1.0 - distance / self.falloff;
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// One material. Every rule that is set multiplies into the weight, unset rules accept
+/// everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplatLayer {
+    pub name: String,
+    /// raw field value
+    pub height: Option<SplatRange>,
+    /// degrees from horizontal
+    pub slope: Option<SplatRange>,
+    /// laplacian per unit area, positive in hollows and valleys, negative on ridges
+    pub curvature: Option<SplatRange>,
+    /// value of the noise field passed to `splat_weights`
+    pub noise: Option<SplatRange>,
+    /// overall scale relative to the other layers
+    pub strength: f32,
+}
+
+impl SplatLayer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            height: None,
+            slope: None,
+            curvature: None,
+            noise: None,
+            strength: 1.0,
+        }
+    }
+
+    pub fn with_height(mut self, range: SplatRange) -> Self {
+        self.height = Some(range);
+        self
+    }
+
+    pub fn with_slope(mut self, range: SplatRange) -> Self {
+        self.slope = Some(range);
+        self
+    }
+
+    pub fn with_curvature(mut self, range: SplatRange) -> Self {
+        self.curvature = Some(range);
+        self
+    }
+
+    pub fn with_noise(mut self, range: SplatRange) -> Self {
+        self.noise = Some(range);
+        self
+    }
+
+    pub fn with_strength(mut self, strength: f32) -> Self {
+        self.strength = strength;
+        self
+    }
+}
+
+/// Per-material weight fields in layer order, summing to 1 in every cell.
+pub struct SplatMap {
+    pub names: Vec<String>,
+    pub weights: Vec<Field>,
+}
+
+impl SplatMap {
+    /// Packs four materials per RGBA image as `{stem}_0.png`, `{stem}_1.png`, ...
+    /// Channels past the last material are left at 0.
+    pub fn write_pngs(
+        &self,
+        dir: &Path,
+        stem: &str,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let width = self.weights[0].width;
+        let height = self.weights[0].height();
+
+        let mut paths = Vec::new();
+        for (index, group) in self.weights.chunks(4).enumerate() {
+            let img = ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+                let i = y as usize * width + x as usize;
+                let mut pixel = [0_u8; 4];
+                for (channel, weights) in pixel.iter_mut().zip(group) {
+                    *channel = (weights.flattened_field[i] * u8::MAX as f32).round() as u8;
+                }
+                Rgba(pixel)
+            });
+
+            let path = dir.join(format!("{}_{}.png", stem, index));
+            img.save(&path)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+impl Field {
+    /// Sum of the Hessian eigenvalues from `structural_lines`, per unit area.
+    fn curvature(&self, cell_size: f32) -> Result<Self, Box<dyn std::error::Error>> {
+        let (crests, thalwegs, convex, concave) = self.structural_lines()?;
+        let scale = 1.0 / (cell_size * cell_size);
+
+        let result: Vec<f32> = (0..self.flattened_field.len())
+            .into_par_iter()
+            .map(|i| {
+                (crests.flattened_field[i]
+                    + thalwegs.flattened_field[i]
+                    + convex.flattened_field[i]
+                    + concave.flattened_field[i])
+                    * scale
+            })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Material weights from the rules in `layers`.
+    ///
+    /// Cells no layer accepts go entirely to the first layer, so it should be the base
+    /// material. `noise` is required when any layer has a noise rule.
+    pub fn splat_weights(
+        &self,
+        layers: &[SplatLayer],
+        cell_size: f32,
+        noise: Option<&Field>,
+    ) -> Result<SplatMap, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        if layers.is_empty() {
+            return Err("Splat map needs at least one layer".into());
+        }
+        for layer in layers {
+            if layer.strength.is_nan() || layer.strength < 0.0 {
+                return Err(
+                    format!("Invalid strength for {}: {}", layer.name, layer.strength).into(),
+                );
+            }
+            for (rule, name) in [
+                (layer.height, "height"),
+                (layer.slope, "slope"),
+                (layer.curvature, "curvature"),
+                (layer.noise, "noise"),
+            ] {
+                if let Some(range) = rule {
+                    range.check(&format!("{} {}", layer.name, name))?;
+                }
+            }
+        }
+
+        // only derive the inputs some layer actually uses
+        let slope = if layers.iter().any(|l| l.slope.is_some()) {
+            Some(
+                self.steepness()?
+                    .map(|rise| (rise / cell_size).atan().to_degrees()),
+            )
+        } else {
+            None
+        };
+        let curvature = if layers.iter().any(|l| l.curvature.is_some()) {
+            Some(self.curvature(cell_size)?)
+        } else {
+            None
+        };
+        let noise = match noise {
+            Some(noise) => {
+                self.check_same_size(noise)?;
+                Some(noise)
+            }
+            None if layers.iter().any(|l| l.noise.is_some()) => {
+                return Err("A layer has a noise rule but no noise field was given".into());
+            }
+            None => None,
+        };
+
+        let len = self.flattened_field.len();
+        let mut weights: Vec<Vec<f32>> = vec![vec![0.0; len]; layers.len()];
+
+        for (layer, weights) in layers.iter().zip(weights.iter_mut()) {
+            weights.par_iter_mut().enumerate().for_each(|(i, w)| {
+                let rule = |range: Option<SplatRange>, input: Option<&Field>| match (range, input) {
+                    (Some(range), Some(input)) => range.weight(input.flattened_field[i]),
+                    _ => 1.0,
+                };
+
+                *w = layer.strength
+                    * rule(layer.height, Some(self))
+                    * rule(layer.slope, slope.as_ref())
+                    * rule(layer.curvature, curvature.as_ref())
+                    * rule(layer.noise, noise);
+            });
+        }
+
+        let totals: Vec<f32> = (0..len)
+            .into_par_iter()
+            .map(|i| weights.iter().map(|w| w[i]).sum())
+            .collect();
+        for (k, weights) in weights.iter_mut().enumerate() {
+            weights
+                .par_iter_mut()
+                .zip(totals.par_iter())
+                .for_each(|(w, &total)| {
+                    *w = if total > 0.0 {
+                        *w / total
+                    } else if k == 0 {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                });
+        }
+
+        Ok(SplatMap {
+            names: layers.iter().map(|l| l.name.clone()).collect(),
+            weights: weights
+                .into_iter()
+                .map(|w| Field {
+                    flattened_field: w.into_boxed_slice(),
+                    width: self.width,
+                })
+                .collect(),
+        })
+    }
+}
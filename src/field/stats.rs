@@ -0,0 +1,353 @@
+use std::path::Path;
+
+use image::{ImageBuffer, Luma};
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+// resolution of the CDF used for histogram equalization
+const EQUALIZATION_BINS: usize = 4096;
+
+/// Summary of the finite values of a field. NaNs are counted but otherwise skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldStats {
+    pub count: usize,
+    pub nan_count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub std_dev: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn bin_width(&self) -> f32 {
+        (self.max - self.min) / self.counts.len() as f32
+    }
+
+    /// `[start, end)` of bin `i`, the last bin also includes `max`
+    pub fn bin_range(&self, i: usize) -> (f32, f32) {
+        let start = self.min + i as f32 * self.bin_width();
+        (start, start + self.bin_width())
+    }
+
+    fn bin_of(&self, value: f32) -> usize {
+        let t = (value - self.min) / (self.max - self.min);
+        ((t * self.counts.len() as f32) as usize).min(self.counts.len() - 1)
+    }
+}
+
+/// How values are mapped onto an output range, see `Field::normalize_with`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// raw min and max map to the ends of the range
+    MinMax,
+    /// values outside the `low`..`high` percentiles (0-100) are clipped first
+    PercentileClip { low: f32, high: f32 },
+    /// mean +- `sigmas` standard deviations map to the ends of the range
+    ZScore { sigmas: f32 },
+    /// ln(1 + v - min), for heavy-tailed layers like flow accumulation
+    Log,
+    /// min-max followed by t^gamma
+    Gamma(f32),
+    /// each value maps to its position in the cumulative distribution
+    HistogramEqualization,
+}
+
+impl Field {
+    pub fn stats(&self) -> FieldStats {
+        // (count, nan_count, min, max, sum, sum of squares)
+        let (count, nan_count, min, max, sum, sum_sq) = self
+            .flattened_field
+            .par_iter()
+            .fold(
+                || (0_usize, 0_usize, f32::MAX, f32::MIN, 0.0_f64, 0.0_f64),
+                |(count, nans, min, max, sum, sum_sq), &v| {
+                    if v.is_nan() {
+                        (count, nans + 1, min, max, sum, sum_sq)
+                    } else if v.is_finite() {
+                        let d = v as f64;
+                        (
+                            count + 1,
+                            nans,
+                            min.min(v),
+                            max.max(v),
+                            sum + d,
+                            sum_sq + d * d,
+                        )
+                    } else {
+                        (count, nans, min, max, sum, sum_sq)
+                    }
+                },
+            )
+            .reduce(
+                || (0, 0, f32::MAX, f32::MIN, 0.0, 0.0),
+                |a, b| {
+                    (
+                        a.0 + b.0,
+                        a.1 + b.1,
+                        a.2.min(b.2),
+                        a.3.max(b.3),
+                        a.4 + b.4,
+                        a.5 + b.5,
+                    )
+                },
+            );
+
+        if count == 0 {
+            return FieldStats {
+                count,
+                nan_count,
+                min: f32::NAN,
+                max: f32::NAN,
+                mean: f32::NAN,
+                std_dev: f32::NAN,
+            };
+        }
+
+        let mean = sum / count as f64;
+        let variance = (sum_sq / count as f64 - mean * mean).max(0.0);
+
+        FieldStats {
+            count,
+            nan_count,
+            min,
+            max,
+            mean: mean as f32,
+            std_dev: variance.sqrt() as f32,
+        }
+    }
+
+    fn sorted_finite_values(&self) -> Vec<f32> {
+        let mut values: Vec<f32> = self
+            .flattened_field
+            .par_iter()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect();
+        values.par_sort_unstable_by(f32::total_cmp);
+        values
+    }
+
+    /// Percentiles (0-100) of the finite values, linearly interpolated between ranks.
+    pub fn percentiles(&self, percentiles: &[f32]) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+            return Err(format!("Percentile out of range: {}", p).into());
+        }
+
+        let values = self.sorted_finite_values();
+        if values.is_empty() {
+            return Err("Field has no finite values".into());
+        }
+
+        Ok(percentiles
+            .iter()
+            .map(|p| {
+                let rank = p / 100.0 * (values.len() - 1) as f32;
+                let lower = rank.floor() as usize;
+                let upper = (lower + 1).min(values.len() - 1);
+                let t = rank - lower as f32;
+                values[lower] * (1.0 - t) + values[upper] * t
+            })
+            .collect())
+    }
+
+    pub fn percentile(&self, percentile: f32) -> Result<f32, Box<dyn std::error::Error>> {
+        Ok(self.percentiles(&[percentile])?[0])
+    }
+
+    /// Histogram of the finite values over their own min..max.
+    pub fn histogram(&self, bins: usize) -> Result<Histogram, Box<dyn std::error::Error>> {
+        let stats = self.stats();
+        if stats.count == 0 {
+            return Err("Field has no finite values".into());
+        }
+        self.histogram_range(bins, stats.min, stats.max)
+    }
+
+    /// Histogram over `min..=max`, values outside the range are not counted.
+    pub fn histogram_range(
+        &self,
+        bins: usize,
+        min: f32,
+        max: f32,
+    ) -> Result<Histogram, Box<dyn std::error::Error>> {
+        if bins == 0 {
+            return Err("Histogram needs at least one bin".into());
+        }
+        if !(min.is_finite() && max.is_finite() && min <= max) {
+            return Err(format!("Invalid histogram range: {}..{}", min, max).into());
+        }
+
+        let mut histogram = Histogram {
+            min,
+            // a degenerate range still needs a non-zero bin width
+            max: if max > min { max } else { min + 1.0 },
+            counts: vec![0; bins],
+        };
+
+        let counts = self
+            .flattened_field
+            .par_iter()
+            .filter(|v| v.is_finite() && **v >= min && **v <= max)
+            .fold(
+                || vec![0_u64; bins],
+                |mut counts, &v| {
+                    counts[histogram.bin_of(v)] += 1;
+                    counts
+                },
+            )
+            .reduce(
+                || vec![0_u64; bins],
+                |mut a, b| {
+                    a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+
+        histogram.counts = counts;
+        Ok(histogram)
+    }
+
+    /// Maps every finite value into [0, 1] according to `mode`. NaNs stay NaN and a
+    /// constant field maps to 0.
+    fn unit_normalized(&self, mode: Normalization) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let stats = self.stats();
+        if stats.count == 0 {
+            return Err("Field has no finite values".into());
+        }
+
+        let unit = |v: f32, low: f32, high: f32| {
+            if high - low > f32::EPSILON {
+                ((v - low) / (high - low)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        let (min, max) = (stats.min, stats.max);
+
+        let result = match mode {
+            Normalization::MinMax => self
+                .flattened_field
+                .par_iter()
+                .map(|&v| unit(v, min, max))
+                .collect(),
+            Normalization::PercentileClip { low, high } => {
+                if low >= high {
+                    return Err(format!("Invalid percentile clip: {}..{}", low, high).into());
+                }
+                let bounds = self.percentiles(&[low, high])?;
+                self.flattened_field
+                    .par_iter()
+                    .map(|&v| unit(v, bounds[0], bounds[1]))
+                    .collect()
+            }
+            Normalization::ZScore { sigmas } => {
+                if sigmas.is_nan() || sigmas <= 0.0 {
+                    return Err(format!("Invalid z-score range: {} sigmas", sigmas).into());
+                }
+                let (low, high) = (
+                    stats.mean - sigmas * stats.std_dev,
+                    stats.mean + sigmas * stats.std_dev,
+                );
+                self.flattened_field
+                    .par_iter()
+                    .map(|&v| unit(v, low, high))
+                    .collect()
+            }
+            Normalization::Log => {
+                let top = (max - min).ln_1p();
+                self.flattened_field
+                    .par_iter()
+                    .map(|&v| unit((v - min).max(0.0).ln_1p(), 0.0, top))
+                    .collect()
+            }
+            Normalization::Gamma(gamma) => {
+                if gamma.is_nan() || gamma <= 0.0 {
+                    return Err(format!("Invalid gamma: {}", gamma).into());
+                }
+                self.flattened_field
+                    .par_iter()
+                    .map(|&v| unit(v, min, max).powf(gamma))
+                    .collect()
+            }
+            Normalization::HistogramEqualization => {
+                let histogram = self.histogram_range(EQUALIZATION_BINS, min, max)?;
+                let total = stats.count as f32;
+
+                let mut below = Vec::with_capacity(histogram.counts.len());
+                let mut running = 0_u64;
+                for &c in histogram.counts.iter() {
+                    below.push(running);
+                    running += c;
+                }
+
+                self.flattened_field
+                    .par_iter()
+                    .map(|&v| {
+                        if !v.is_finite() {
+                            return v;
+                        }
+                        // spread the cells of a bin linearly across its share of the CDF
+                        let bin = histogram.bin_of(v);
+                        let (start, _) = histogram.bin_range(bin);
+                        let within = ((v - start) / histogram.bin_width()).clamp(0.0, 1.0);
+                        (below[bin] as f32 + within * histogram.counts[bin] as f32) / total
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// Rescales the field into `new_min..=new_max` using `mode`.
+    pub fn normalize_with(
+        &self,
+        mode: Normalization,
+        new_min: f32,
+        new_max: f32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let new_range = new_max - new_min;
+        let result: Vec<f32> = self
+            .unit_normalized(mode)?
+            .into_par_iter()
+            .map(|t| t * new_range + new_min)
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Min-max into [0, 1] with a constant field mapping to all zeros, for viewing raw
+    /// kernel output like `sobel` or `structural_lines`.
+    pub fn normalized(&self) -> Result<Self, Box<dyn std::error::Error>> {
+        self.normalize_with(Normalization::MinMax, 0.0, 1.0)
+    }
+
+    /// 16-bit grayscale PNG with the values mapped through `mode` instead of raw min/max.
+    pub fn write_png_u16_with(
+        &self,
+        path: &Path,
+        mode: Normalization,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let unit = self.unit_normalized(mode)?;
+
+        let img = ImageBuffer::from_fn(self.width as u32, self.height() as u32, |x, y| {
+            let t = unit[y as usize * self.width + x as usize];
+            Luma::<u16>([(t * u16::MAX as f32) as u16])
+        });
+
+        img.save(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::hydrology::D8_OFFSETS;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalErosion {
+    pub iterations: usize,
+    /// steepest stable slope in degrees, anything steeper sheds material downhill
+    pub talus_angle: f32,
+    pub cell_size: f32,
+    /// fraction of the excess moved per iteration, 0.5 settles without oscillating
+    pub rate: f32,
+}
+
+impl Default for ThermalErosion {
+    fn default() -> Self {
+        Self {
+            iterations: 50,
+            talus_angle: 35.0,
+            cell_size: 1.0,
+            rate: 0.5,
+        }
+    }
+}
+
+pub struct ThermalResult {
+    pub field: Field,
+    /// net material lost from each cell
+    pub erosion: Field,
+    /// net material piled up in each cell, the scree at the foot of steep faces
+    pub talus: Field,
+}
+
+impl Field {
+    /// Thermal weathering: every cell steeper than the talus angle towards one or more of its
+    /// 8 neighbours passes part of the excess height to them, proportionally to how far each
+    /// one is over the angle (Musgrave et al. 1989). Total mass is preserved.
+    ///
+    /// `hardness`, in [0, 1], raises the talus angle of a cell towards vertical, so 1 never
+    /// erodes.
+    pub fn thermal_erosion(
+        &self,
+        params: &ThermalErosion,
+        hardness: Option<&Field>,
+    ) -> Result<ThermalResult, Box<dyn std::error::Error>> {
+        Self::check_cell_size(params.cell_size)?;
+        if params.talus_angle.is_nan() || !(0.0..90.0).contains(&params.talus_angle) {
+            return Err(format!("Invalid talus angle: {}", params.talus_angle).into());
+        }
+        if params.rate.is_nan() || params.rate <= 0.0 || params.rate > 1.0 {
+            return Err(format!("Invalid thermal erosion rate: {}", params.rate).into());
+        }
+        if let Some(hardness) = hardness {
+            self.check_same_size(hardness)?;
+        }
+
+        let len = self.flattened_field.len();
+        // height difference that is just stable for each cell and direction
+        let distances: Vec<f32> = D8_OFFSETS
+            .iter()
+            .map(|&(dx, dy)| ((dx * dx + dy * dy) as f32).sqrt() * params.cell_size)
+            .collect();
+        let tan_talus: Vec<f32> = (0..len)
+            .into_par_iter()
+            .map(|i| {
+                let h = hardness.map_or(0.0, |f| f.flattened_field[i].clamp(0.0, 1.0));
+                let angle = params.talus_angle + h * (90.0 - params.talus_angle);
+                // vertical is infinitely stable, and f32 tan turns negative right at 90 degrees
+                let tan = angle.to_radians().tan();
+                if angle >= 90.0 || tan < 0.0 {
+                    f32::INFINITY
+                } else {
+                    tan
+                }
+            })
+            .collect();
+
+        let mut terrain = self.clone();
+        for _ in 0..params.iterations {
+            let outflow: Vec<[f32; 8]> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let h = terrain.flattened_field[i];
+                    let mut excess = [0.0_f32; 8];
+                    for (k, &offset) in D8_OFFSETS.iter().enumerate() {
+                        if let Some(n) = terrain.neighbor(i, offset) {
+                            let stable = tan_talus[i] * distances[k];
+                            excess[k] = (h - terrain.flattened_field[n] - stable).max(0.0);
+                        }
+                    }
+
+                    let total: f32 = excess.iter().sum();
+                    if total > 0.0 {
+                        // moving half the largest excess levels the steepest pair
+                        let largest = excess.iter().fold(0.0_f32, |a, &b| a.max(b));
+                        let moved = params.rate * largest * 0.5;
+                        excess.iter_mut().for_each(|e| *e *= moved / total);
+                    }
+                    excess
+                })
+                .collect();
+
+            let next: Vec<f32> = (0..len)
+                .into_par_iter()
+                .map(|i| {
+                    let mut h = terrain.flattened_field[i] - outflow[i].iter().sum::<f32>();
+                    for (k, &offset) in D8_OFFSETS.iter().enumerate() {
+                        if let Some(n) = terrain.neighbor(i, offset) {
+                            // the neighbour's direction back to us is the opposite compass point
+                            h += outflow[n][(k + 4) % 8];
+                        }
+                    }
+                    h
+                })
+                .collect();
+            terrain.flattened_field = next.into_boxed_slice();
+        }
+
+        let change = terrain.zip_with(self, |after, before| after - before)?;
+
+        Ok(ThermalResult {
+            erosion: change.map(|d| (-d).max(0.0)),
+            talus: change.map(|d| d.max(0.0)),
+            field: terrain,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cliff() -> Field {
+        Field::from_fn(24, 16, |x, y| {
+            if x < 12 {
+                40.0 + (y as f32 * 0.7).sin()
+            } else {
+                (x * y) as f32 * 0.05
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn preserves_mass() {
+        let field = cliff();
+        let result = field
+            .thermal_erosion(&ThermalErosion::default(), None)
+            .unwrap();
+
+        let total = |f: &Field| f.flattened_field.iter().map(|&v| v as f64).sum::<f64>();
+        assert!(result.erosion.flattened_field.iter().any(|&e| e > 0.0));
+        assert!((total(&result.field) - total(&field)).abs() < 1e-6 * total(&field));
+        assert!((total(&result.erosion) - total(&result.talus)).abs() < 1e-6 * total(&field));
+    }
+
+    #[test]
+    fn full_hardness_never_erodes() {
+        let field = cliff();
+        let hardness = field.map(|_| 1.0);
+        let result = field
+            .thermal_erosion(&ThermalErosion::default(), Some(&hardness))
+            .unwrap();
+
+        assert_eq!(result.field.flattened_field, field.flattened_field);
+        assert!(result.erosion.flattened_field.iter().all(|&e| e == 0.0));
+    }
+}
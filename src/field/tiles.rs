@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+/// How a field is cut into fixed-size tiles.
+///
+/// Neighbouring tiles start `tile_size - overlap` cells apart, so an overlap of 1 gives the
+/// shared edge rows engines expect for 513 x 513 landscape components. Each tile is then
+/// padded by `border` extra cells on every side, clamped at the edges of the field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileGrid {
+    pub tile_size: usize,
+    pub overlap: usize,
+    pub border: usize,
+    pub columns: usize,
+    pub rows: usize,
+    /// size of the field being tiled
+    pub width: usize,
+    pub height: usize,
+}
+
+pub struct Tile {
+    pub column: usize,
+    pub row: usize,
+    /// `tile_size + 2 * border` cells square
+    pub field: Field,
+}
+
+impl TileGrid {
+    pub fn new(
+        width: usize,
+        height: usize,
+        tile_size: usize,
+        overlap: usize,
+        border: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if tile_size == 0 || overlap >= tile_size {
+            return Err(format!(
+                "Invalid tiling: tile size {} with overlap {}",
+                tile_size, overlap
+            )
+            .into());
+        }
+        if width == 0 || height == 0 {
+            return Err("Cannot tile an empty field".into());
+        }
+
+        let stride = tile_size - overlap;
+        let count = |n: usize| n.saturating_sub(overlap).div_ceil(stride).max(1);
+
+        Ok(Self {
+            tile_size,
+            overlap,
+            border,
+            columns: count(width),
+            rows: count(height),
+            width,
+            height,
+        })
+    }
+
+    pub fn stride(&self) -> usize {
+        self.tile_size - self.overlap
+    }
+
+    /// top-left cell of a tile's interior (border excluded) in field coordinates
+    pub fn origin(&self, column: usize, row: usize) -> (usize, usize) {
+        (column * self.stride(), row * self.stride())
+    }
+
+    /// Expands `{col}`, `{row}` and `{lod}` in `pattern`, e.g. `"terrain_L{lod}_x{col}_y{row}.r32"`.
+    pub fn tile_name(pattern: &str, column: usize, row: usize, lod: usize) -> String {
+        pattern
+            .replace("{col}", &column.to_string())
+            .replace("{row}", &row.to_string())
+            .replace("{lod}", &lod.to_string())
+    }
+
+    /// Writes every tile as r32 into `dir`, returning the paths in tile order.
+    pub fn write_tiles(
+        &self,
+        tiles: &[Tile],
+        dir: &Path,
+        pattern: &str,
+        lod: usize,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(dir)?;
+
+        tiles
+            .iter()
+            .map(|tile| {
+                let path = dir.join(Self::tile_name(pattern, tile.column, tile.row, lod));
+                tile.field.write_r32(&path)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    /// Reads back a full set of tiles written by `write_tiles` with the same grid.
+    pub fn read_tiles(
+        &self,
+        dir: &Path,
+        pattern: &str,
+        lod: usize,
+    ) -> Result<Vec<Tile>, Box<dyn std::error::Error>> {
+        let size = self.tile_size + 2 * self.border;
+        let mut tiles = Vec::with_capacity(self.columns * self.rows);
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let path = dir.join(Self::tile_name(pattern, column, row, lod));
+                let field = Field::from_r32_with_width(&path, size)?;
+                if field.height() != size {
+                    return Err(format!(
+                        "Tile {} is {}x{}, expected {}x{}",
+                        path.display(),
+                        field.width,
+                        field.height(),
+                        size,
+                        size
+                    )
+                    .into());
+                }
+                tiles.push(Tile { column, row, field });
+            }
+        }
+
+        Ok(tiles)
+    }
+}
+
+impl Field {
+    /// Cuts the field into the tiles of `grid`, row by row. Tiles hanging over the
+    /// right or bottom edge are filled by clamping to the last row/column.
+    pub fn tiles(&self, grid: &TileGrid) -> Result<Vec<Tile>, Box<dyn std::error::Error>> {
+        if grid.width != self.width || grid.height != self.height() {
+            return Err(format!(
+                "Tile grid is for a {}x{} field, got {}x{}",
+                grid.width,
+                grid.height,
+                self.width,
+                self.height()
+            )
+            .into());
+        }
+
+        let size = grid.tile_size + 2 * grid.border;
+
+        let tiles = (0..grid.rows * grid.columns)
+            .into_par_iter()
+            .map(|t| {
+                let column = t % grid.columns;
+                let row = t / grid.columns;
+                let (ox, oy) = grid.origin(column, row);
+                let left = ox as isize - grid.border as isize;
+                let top = oy as isize - grid.border as isize;
+
+                let mut values = Vec::with_capacity(size * size);
+                for y in 0..size as isize {
+                    for x in 0..size as isize {
+                        values.push(self.sample(left + x, top + y));
+                    }
+                }
+
+                Tile {
+                    column,
+                    row,
+                    field: Self {
+                        flattened_field: values.into_boxed_slice(),
+                        width: size,
+                    },
+                }
+            })
+            .collect();
+
+        Ok(tiles)
+    }
+
+    /// Reassembles tiles cut with `grid`, dropping their borders. Where tiles overlap the
+    /// one later in `tiles` wins; missing tiles leave their area at 0.
+    pub fn from_tiles(grid: &TileGrid, tiles: &[Tile]) -> Result<Self, Box<dyn std::error::Error>> {
+        let size = grid.tile_size + 2 * grid.border;
+        let mut values = vec![0.0_f32; grid.width * grid.height];
+
+        for tile in tiles {
+            if tile.column >= grid.columns || tile.row >= grid.rows {
+                return Err(format!(
+                    "Tile ({}, {}) is outside the {}x{} grid",
+                    tile.column, tile.row, grid.columns, grid.rows
+                )
+                .into());
+            }
+            if tile.field.width != size || tile.field.height() != size {
+                return Err(format!(
+                    "Tile ({}, {}) is {}x{}, expected {}x{}",
+                    tile.column,
+                    tile.row,
+                    tile.field.width,
+                    tile.field.height(),
+                    size,
+                    size
+                )
+                .into());
+            }
+
+            let (ox, oy) = grid.origin(tile.column, tile.row);
+            let columns = grid.tile_size.min(grid.width - ox);
+            for y in 0..grid.tile_size.min(grid.height - oy) {
+                let src = (y + grid.border) * size + grid.border;
+                let dst = (oy + y) * grid.width + ox;
+                values[dst..dst + columns]
+                    .copy_from_slice(&tile.field.flattened_field[src..src + columns]);
+            }
+        }
+
+        Ok(Self {
+            flattened_field: values.into_boxed_slice(),
+            width: grid.width,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_reassemble_to_the_input() {
+        let field = Field::from_fn(23, 14, |x, y| (x * 31 + y * 7) as f32).unwrap();
+        for (tile_size, overlap, border) in [(8, 0, 0), (9, 1, 2), (5, 2, 1), (64, 0, 3)] {
+            let grid = TileGrid::new(23, 14, tile_size, overlap, border).unwrap();
+            let tiles = field.tiles(&grid).unwrap();
+            assert_eq!(tiles.len(), grid.columns * grid.rows);
+
+            let restored = Field::from_tiles(&grid, &tiles).unwrap();
+            assert_eq!(restored.flattened_field, field.flattened_field);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::field::field::Field;
+use crate::field::mesh::{Mesh, UpAxis};
+use crate::field::view::Raster;
+
+const NONE: u32 = u32::MAX;
+
+/// Settings for `Field::to_tin`.
+#[derive(Debug, Clone, Copy)]
+pub struct TinOptions {
+    /// stop once no cell is further than this from the surface, in field units
+    pub max_error: f32,
+    /// stop before the mesh grows past this many triangles
+    pub max_triangles: Option<usize>,
+    /// cells whose ridge or valley strength from `structural_lines` reaches this are
+    /// treated as feature lines
+    pub line_threshold: Option<f32>,
+    /// errors on feature lines count this many times over, holding them to
+    /// `max_error / line_weight`
+    pub line_weight: f32,
+    pub horizontal_scale: f32,
+    pub vertical_scale: f32,
+    pub up_axis: UpAxis,
+}
+
+impl Default for TinOptions {
+    fn default() -> Self {
+        Self {
+            max_error: 0.01,
+            max_triangles: None,
+            line_threshold: None,
+            line_weight: 4.0,
+            horizontal_scale: 1.0,
+            vertical_scale: 1.0,
+            up_axis: UpAxis::Y,
+        }
+    }
+}
+
+pub struct Tin {
+    pub mesh: Mesh,
+    /// largest weighted vertical error left, in field units
+    pub max_error: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Triangle {
+    /// counter-clockwise in (x, y) grid coordinates
+    v: [u32; 3],
+    /// neighbour across the edge opposite each vertex
+    adj: [u32; 3],
+    alive: bool,
+    /// bumped on reuse so stale heap entries can be told apart
+    stamp: u32,
+}
+
+/// worst cell of a triangle, max-heap ordered by score
+struct Candidate {
+    score: f32,
+    triangle: u32,
+    stamp: u32,
+    cell: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// exact on integer grid coordinates, positive when c is left of a -> b
+fn orient(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// exact, positive when d is strictly inside the circumcircle of counter-clockwise abc
+fn in_circle(a: (i64, i64), b: (i64, i64), c: (i64, i64), d: (i64, i64)) -> bool {
+    let row = |p: (i64, i64)| {
+        let (x, y) = ((p.0 - d.0) as i128, (p.1 - d.1) as i128);
+        (x, y, x * x + y * y)
+    };
+    let (ax, ay, a2) = row(a);
+    let (bx, by, b2) = row(b);
+    let (cx, cy, c2) = row(c);
+    ax * (by * c2 - b2 * cy) - ay * (bx * c2 - b2 * cx) + a2 * (bx * cy - by * cx) > 0
+}
+
+/// Delaunay triangulation refined by greedy insertion (Garland & Heckbert 1995).
+struct Triangulation<'a> {
+    field: &'a Field,
+    weights: Option<Vec<f32>>,
+    points: Vec<(i64, i64)>,
+    is_vertex: Vec<bool>,
+    triangles: Vec<Triangle>,
+    free: Vec<u32>,
+    live: usize,
+    heap: BinaryHeap<Candidate>,
+}
+
+impl<'a> Triangulation<'a> {
+    fn new(field: &'a Field, weights: Option<Vec<f32>>) -> Self {
+        let (w, h) = (field.width as i64 - 1, field.height() as i64 - 1);
+        let mut is_vertex = vec![false; field.flattened_field.len()];
+        let points = vec![(0, 0), (w, 0), (w, h), (0, h)];
+        for &(x, y) in &points {
+            is_vertex[y as usize * field.width + x as usize] = true;
+        }
+
+        let mut triangulation = Self {
+            field,
+            weights,
+            points,
+            is_vertex,
+            triangles: vec![
+                Triangle {
+                    v: [0, 1, 2],
+                    adj: [NONE, 1, NONE],
+                    alive: true,
+                    stamp: 0,
+                },
+                Triangle {
+                    v: [0, 2, 3],
+                    adj: [NONE, NONE, 0],
+                    alive: true,
+                    stamp: 0,
+                },
+            ],
+            free: Vec::new(),
+            live: 2,
+            heap: BinaryHeap::new(),
+        };
+        triangulation.scan(0);
+        triangulation.scan(1);
+        triangulation
+    }
+
+    fn height(&self, p: (i64, i64)) -> f32 {
+        self.field.get(p.0 as usize, p.1 as usize)
+    }
+
+    /// Finds the cell furthest from the triangle's plane and queues it.
+    fn scan(&mut self, t: u32) {
+        let triangle = self.triangles[t as usize];
+        let [a, b, c] = triangle.v.map(|i| self.points[i as usize]);
+        let [za, zb, zc] = [a, b, c].map(|p| self.height(p));
+        let area = orient(a, b, c) as f32;
+
+        let mut best: Option<(f32, usize)> = None;
+        for y in a.1.min(b.1).min(c.1)..=a.1.max(b.1).max(c.1) {
+            for x in a.0.min(b.0).min(c.0)..=a.0.max(b.0).max(c.0) {
+                let p = (x, y);
+                let (wa, wb, wc) = (orient(b, c, p), orient(c, a, p), orient(a, b, p));
+                if wa < 0 || wb < 0 || wc < 0 {
+                    continue;
+                }
+                let cell = y as usize * self.field.width + x as usize;
+                if self.is_vertex[cell] {
+                    continue;
+                }
+                let z = (wa as f32 * za + wb as f32 * zb + wc as f32 * zc) / area;
+                let mut score = (self.field.flattened_field[cell] - z).abs();
+                if let Some(weights) = &self.weights {
+                    score *= weights[cell];
+                }
+                if best.is_none_or(|(s, _)| score > s) {
+                    best = Some((score, cell));
+                }
+            }
+        }
+
+        if let Some((score, cell)) = best {
+            if score > 0.0 {
+                self.heap.push(Candidate {
+                    score,
+                    triangle: t,
+                    stamp: triangle.stamp,
+                    cell,
+                });
+            }
+        }
+    }
+
+    /// Drops stale entries and returns the worst live candidate's score, triangle and cell.
+    fn worst(&mut self) -> Option<(f32, u32, usize)> {
+        while let Some(top) = self.heap.peek() {
+            let triangle = &self.triangles[top.triangle as usize];
+            if triangle.alive && triangle.stamp == top.stamp {
+                break;
+            }
+            self.heap.pop();
+        }
+        self.heap.peek().map(|c| (c.score, c.triangle, c.cell))
+    }
+
+    fn allocate(&mut self, triangle: Triangle) -> u32 {
+        match self.free.pop() {
+            Some(t) => {
+                let stamp = self.triangles[t as usize].stamp + 1;
+                self.triangles[t as usize] = Triangle { stamp, ..triangle };
+                t
+            }
+            None => {
+                self.triangles.push(triangle);
+                self.triangles.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Bowyer-Watson insertion of `cell`, which lies inside or on the edge of `start`.
+    fn insert(&mut self, start: u32, cell: usize) {
+        let p = (
+            (cell % self.field.width) as i64,
+            (cell / self.field.width) as i64,
+        );
+        self.is_vertex[cell] = true;
+        self.points.push(p);
+        let new_vertex = self.points.len() as u32 - 1;
+
+        // every triangle whose circumcircle holds p, and the outline of that cavity
+        let mut cavity = vec![start];
+        let mut boundary = Vec::new();
+        self.triangles[start as usize].alive = false;
+        let mut i = 0;
+        while i < cavity.len() {
+            let triangle = self.triangles[cavity[i] as usize];
+            for k in 0..3 {
+                let (a, b) = (triangle.v[(k + 1) % 3], triangle.v[(k + 2) % 3]);
+                let n = triangle.adj[k];
+                if n != NONE && self.triangles[n as usize].alive {
+                    let [na, nb, nc] = self.triangles[n as usize]
+                        .v
+                        .map(|v| self.points[v as usize]);
+                    if in_circle(na, nb, nc, p) {
+                        self.triangles[n as usize].alive = false;
+                        cavity.push(n);
+                        continue;
+                    }
+                } else if n != NONE {
+                    // already part of the cavity
+                    continue;
+                }
+                boundary.push((a, b, n));
+            }
+            i += 1;
+        }
+        self.free.extend(&cavity);
+
+        // fan from p, a hull edge through p gets no triangle
+        let mut fan: Vec<(u32, u32)> = Vec::with_capacity(boundary.len());
+        for &(a, b, n) in &boundary {
+            if orient(self.points[a as usize], self.points[b as usize], p) <= 0 {
+                continue;
+            }
+            let t = self.allocate(Triangle {
+                v: [a, b, new_vertex],
+                adj: [NONE, NONE, n],
+                alive: true,
+                stamp: 0,
+            });
+            if n != NONE {
+                let outside = &mut self.triangles[n as usize];
+                let k = (0..3)
+                    .find(|&k| outside.v[(k + 1) % 3] == b && outside.v[(k + 2) % 3] == a)
+                    .unwrap();
+                outside.adj[k] = t;
+            }
+            fan.push((a, t));
+        }
+        for &(_, t) in &fan {
+            let b = self.triangles[t as usize].v[1];
+            if let Some(&(_, next)) = fan.iter().find(|&&(a, _)| a == b) {
+                self.triangles[t as usize].adj[0] = next;
+                self.triangles[next as usize].adj[1] = t;
+            }
+        }
+
+        self.live = self.live + fan.len() - cavity.len();
+        for &(_, t) in &fan {
+            self.scan(t);
+        }
+    }
+}
+
+impl Field {
+    /// Irregular triangle mesh that follows the surface to within `max_error`, refined by
+    /// inserting the worst-fitting cell into a Delaunay triangulation until the error or
+    /// triangle budget is reached.
+    pub fn to_tin(&self, options: &TinOptions) -> Result<Tin, Box<dyn std::error::Error>> {
+        if self.width < 2 || self.height() < 2 {
+            return Err("A mesh needs a field of at least 2x2".into());
+        }
+        if options.max_error.is_nan() || options.max_error < 0.0 {
+            return Err(format!("Invalid max error: {}", options.max_error).into());
+        }
+        if options.max_triangles.is_some_and(|n| n < 2) {
+            return Err("A triangle budget needs at least two triangles".into());
+        }
+        if !(options.line_weight.is_finite() && options.line_weight >= 1.0) {
+            return Err(format!("Invalid line weight: {}", options.line_weight).into());
+        }
+        Self::check_cell_size(options.horizontal_scale)?;
+        if !options.vertical_scale.is_finite() {
+            return Err(format!("Invalid vertical scale: {}", options.vertical_scale).into());
+        }
+
+        let weights = match options.line_threshold {
+            Some(threshold) => {
+                let (crests, thalwegs, _, _) = self.structural_lines()?;
+                let weights = crests
+                    .flattened_field
+                    .iter()
+                    .zip(thalwegs.flattened_field.iter())
+                    .map(|(&crest, &thalweg)| {
+                        if crest >= threshold || -thalweg >= threshold {
+                            options.line_weight
+                        } else {
+                            1.0
+                        }
+                    })
+                    .collect();
+                Some(weights)
+            }
+            None => None,
+        };
+
+        let mut triangulation = Triangulation::new(self, weights);
+        let budget = options.max_triangles.unwrap_or(usize::MAX);
+        while let Some((score, t, cell)) = triangulation.worst() {
+            // an insertion adds at most two triangles
+            if score <= options.max_error || triangulation.live + 2 > budget {
+                break;
+            }
+            triangulation.heap.pop();
+            triangulation.insert(t, cell);
+        }
+        let max_error = triangulation.worst().map_or(0.0, |(score, _, _)| score);
+
+        let (hs, vs) = (options.horizontal_scale, options.vertical_scale);
+        let (width, height) = (self.width as f32 - 1.0, self.height() as f32 - 1.0);
+        let mut mesh = Mesh::default();
+        for &(x, y) in &triangulation.points {
+            let (xf, yf) = (x as f32, y as f32);
+            mesh.push_vertex(
+                [xf * hs, self.get(x as usize, y as usize) * vs, yf * hs],
+                [0.0, 1.0, 0.0],
+                [xf / width, yf / height],
+            );
+        }
+        // rows run along +z in the mesh, which flips the winding
+        mesh.triangles = triangulation
+            .triangles
+            .iter()
+            .filter(|t| t.alive)
+            .map(|t| [t.v[0], t.v[2], t.v[1]])
+            .collect();
+        mesh.recompute_normals();
+
+        Ok(Tin {
+            mesh: mesh.oriented(options.up_axis),
+            max_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hills() -> Field {
+        Field::from_fn(33, 25, |x, y| {
+            let (x, y) = (x as f32 * 0.3, y as f32 * 0.4);
+            x.sin() * y.cos() * 2.0 + (x * 0.5 + y).sin()
+        })
+        .unwrap()
+    }
+
+    /// Height of the mesh surface above grid cell (x, y).
+    fn surface_at(mesh: &Mesh, x: f32, y: f32) -> f32 {
+        for t in &mesh.triangles {
+            let [a, b, c] = t.map(|i| mesh.positions[i as usize]);
+            let det = (b[2] - c[2]) * (a[0] - c[0]) + (c[0] - b[0]) * (a[2] - c[2]);
+            let u = ((b[2] - c[2]) * (x - c[0]) + (c[0] - b[0]) * (y - c[2])) / det;
+            let v = ((c[2] - a[2]) * (x - c[0]) + (a[0] - c[0]) * (y - c[2])) / det;
+            let w = 1.0 - u - v;
+            if u >= -1e-5 && v >= -1e-5 && w >= -1e-5 {
+                return u * a[1] + v * b[1] + w * c[1];
+            }
+        }
+        panic!("cell ({}, {}) is not covered by the mesh", x, y);
+    }
+
+    #[test]
+    fn stays_within_max_error() {
+        let field = hills();
+        let options = TinOptions {
+            max_error: 0.05,
+            ..Default::default()
+        };
+        let tin = field.to_tin(&options).unwrap();
+
+        assert!(tin.max_error <= options.max_error);
+        assert!(tin.mesh.positions.len() < field.flattened_field.len());
+        for y in 0..field.height() {
+            for x in 0..field.width {
+                let error = (surface_at(&tin.mesh, x as f32, y as f32) - field.get(x, y)).abs();
+                assert!(
+                    error <= options.max_error + 1e-4,
+                    "{} at ({}, {})",
+                    error,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn respects_triangle_budget() {
+        let field = hills();
+        let options = TinOptions {
+            max_error: 0.0,
+            max_triangles: Some(64),
+            ..Default::default()
+        };
+        let tin = field.to_tin(&options).unwrap();
+
+        let (right, bottom) = (field.width as f32 - 1.0, field.height() as f32 - 1.0);
+        let boundary = tin
+            .mesh
+            .positions
+            .iter()
+            .filter(|p| p[0] == 0.0 || p[2] == 0.0 || p[0] == right || p[2] == bottom)
+            .count();
+        let (vertices, triangles) = (tin.mesh.positions.len(), tin.mesh.triangles.len());
+        assert!((63..=64).contains(&triangles), "{} triangles", triangles);
+        // a triangulated rectangle with no vertex left out has 2V - B - 2 triangles
+        assert_eq!(triangles, 2 * vertices - boundary - 2);
+        assert!(tin.max_error > 0.0);
+    }
+}
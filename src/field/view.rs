@@ -0,0 +1,372 @@
+use crate::field::field::{self, Field};
+use crate::field::hydrology;
+use crate::field::seamless::{Boundary, Wrapped};
+
+/// Read side of the `Field` API, shared by whole fields and borrowed windows into them.
+///
+/// The neighbourhood kernels read through this too, so they run on a window in place.
+pub trait Raster: Sync {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    /// the `width` cells of row `y`
+    fn row(&self, y: usize) -> &[f32];
+
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.row(y)[x]
+    }
+
+    /// how `sample` and the kernels resolve reads past the edge
+    fn boundary(&self) -> Boundary {
+        Boundary::Clamp
+    }
+
+    /// reads outside the raster are resolved by `boundary`
+    fn sample(&self, x: isize, y: isize) -> f32 {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        match self.boundary() {
+            Boundary::Clamp => self.get(
+                x.clamp(0, width - 1) as usize,
+                y.clamp(0, height - 1) as usize,
+            ),
+            Boundary::Wrap => self.get(x.rem_euclid(width) as usize, y.rem_euclid(height) as usize),
+        }
+    }
+
+    /// This raster tiled endlessly: the local kernels read across opposite edges, and the
+    /// flow-routing ones refuse to run since a periodic field has no edge to drain to.
+    fn wrapped(&self) -> Wrapped<'_, Self> {
+        Wrapped(self)
+    }
+
+    fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.height()).flat_map(move |y| self.row(y).iter().copied())
+    }
+
+    fn min_max(&self) -> (f32, f32) {
+        self.values()
+            .fold((f32::MAX, f32::MIN), |(min, max), value| {
+                (min.min(value), max.max(value))
+            })
+    }
+
+    /// owned copy of just these cells
+    fn to_field(&self) -> Field {
+        let mut values = Vec::with_capacity(self.width() * self.height());
+        for y in 0..self.height() {
+            values.extend_from_slice(self.row(y));
+        }
+
+        Field {
+            flattened_field: values.into_boxed_slice(),
+            width: self.width(),
+        }
+    }
+
+    /// Sobel gradient magnitude in raw height units, see `normalized` for a [0, 1] image.
+    fn sobel(&self) -> Result<Field, Box<dyn std::error::Error>> {
+        field::sobel(self)
+    }
+
+    /// Prewitt gradient magnitude in raw height units.
+    fn prewitt(&self) -> Result<Field, Box<dyn std::error::Error>> {
+        field::prewitt(self)
+    }
+
+    /// Forward-difference gradient magnitude in height units per cell.
+    fn steepness(&self) -> Result<Field, Box<dyn std::error::Error>> {
+        field::steepness(self)
+    }
+
+    /// Hessian eigenvalues split into (crests, thalwegs, convex, concave). Thalweg and
+    /// concave values are negative, nothing is rescaled.
+    fn structural_lines(&self) -> Result<(Field, Field, Field, Field), Box<dyn std::error::Error>> {
+        field::structural_lines(self)
+    }
+
+    /// Slope angle in radians, using Horn's 3x3 finite differences.
+    ///
+    /// `cell_size` is the horizontal spacing between cells in the same unit as the heights.
+    fn slope(&self, cell_size: f32) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::slope(self, cell_size)
+    }
+
+    /// Removes pits and flats with an epsilon priority flood (Barnes et al. 2014),
+    /// so every cell has a strictly lower path to the edge of the field. Fails on a wrapped
+    /// raster, as does everything below that routes flow.
+    fn fill_depressions(&self) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::fill_depressions(self)
+    }
+
+    /// D8 flow accumulation on the depression-filled field.
+    ///
+    /// Each cell holds the number of cells draining through it, itself included.
+    fn flow_accumulation(&self) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::flow_accumulation(self)
+    }
+
+    /// Topographic wetness index, ln(a / tan b).
+    fn topographic_wetness_index(
+        &self,
+        cell_size: f32,
+    ) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::topographic_wetness_index(self, cell_size)
+    }
+
+    /// Stream power index, a * tan b.
+    fn stream_power_index(&self, cell_size: f32) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::stream_power_index(self, cell_size)
+    }
+
+    /// RUSLE slope length and steepness factor in the upslope-area form of Mitasova et al.
+    /// (1996), (m + 1) * (a / 22.13)^m * (sin b / 0.0896)^n with m = 0.4 and n = 1.3.
+    ///
+    /// Expects `cell_size` in metres, since the constants are calibrated to the standard USLE plot.
+    fn ls_factor(&self, cell_size: f32) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::ls_factor(self, cell_size)
+    }
+
+    /// Height above nearest drainage.
+    ///
+    /// Cells with a flow accumulation of at least `channel_threshold` cells are treated as
+    /// drainage; every other cell is measured against the first drainage cell on its D8 flow
+    /// path. Paths that leave the field before reaching a channel use their outlet instead.
+    fn height_above_nearest_drainage(
+        &self,
+        channel_threshold: f32,
+    ) -> Result<Field, Box<dyn std::error::Error>> {
+        hydrology::height_above_nearest_drainage(self, channel_threshold)
+    }
+}
+
+impl Raster for Field {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.flattened_field.len() / self.width
+    }
+
+    fn row(&self, y: usize) -> &[f32] {
+        &self.flattened_field[y * self.width..(y + 1) * self.width]
+    }
+}
+
+/// Borrowed rectangular window into a field's buffer.
+///
+/// `data` is the whole parent buffer with rows `stride` cells apart, so kernels that
+/// need a margin around the window can still read the cells just outside it.
+#[derive(Clone, Copy)]
+pub struct FieldView<'a> {
+    data: &'a [f32],
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+}
+
+pub struct FieldViewMut<'a> {
+    data: &'a mut [f32],
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+}
+
+fn check_window(
+    parent_width: usize,
+    parent_height: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if width == 0 || height == 0 || x + width > parent_width || y + height > parent_height {
+        return Err(format!(
+            "Window {}x{} at ({}, {}) does not fit in {}x{}",
+            width, height, x, y, parent_width, parent_height
+        )
+        .into());
+    }
+    Ok(())
+}
+
+impl<'a> FieldView<'a> {
+    fn parent_height(&self) -> usize {
+        self.data.len() / self.stride
+    }
+
+    /// Window relative to this one.
+    pub fn window(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<FieldView<'a>, Box<dyn std::error::Error>> {
+        check_window(self.width, self.height, x, y, width, height)?;
+
+        Ok(FieldView {
+            data: self.data,
+            x: self.x + x,
+            y: self.y + y,
+            width,
+            height,
+            stride: self.stride,
+        })
+    }
+
+    /// Runs `kernel` on the window plus up to `margin` surrounding cells of the parent and
+    /// crops the margin off the result. The margin keeps 3x3 style kernels from seeing an
+    /// artificial edge at the window boundary; the kernel reads the parent buffer in place.
+    pub fn apply<F>(&self, margin: usize, kernel: F) -> Result<Field, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&FieldView<'a>) -> Result<Field, Box<dyn std::error::Error>>,
+    {
+        let left = self.x.saturating_sub(margin);
+        let top = self.y.saturating_sub(margin);
+        let right = (self.x + self.width + margin).min(self.stride);
+        let bottom = (self.y + self.height + margin).min(self.parent_height());
+
+        let padded = FieldView {
+            data: self.data,
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+            stride: self.stride,
+        };
+
+        let result = kernel(&padded)?;
+        if result.width != padded.width || result.height() != padded.height {
+            return Err("Kernel changed the size of the window".into());
+        }
+
+        Ok(result
+            .view()
+            .window(self.x - left, self.y - top, self.width, self.height)?
+            .to_field())
+    }
+}
+
+impl Raster for FieldView<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[f32] {
+        let start = (self.y + y) * self.stride + self.x;
+        &self.data[start..start + self.width]
+    }
+}
+
+impl FieldViewMut<'_> {
+    pub fn row_mut(&mut self, y: usize) -> &mut [f32] {
+        let start = (self.y + y) * self.stride + self.x;
+        &mut self.data[start..start + self.width]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f32) {
+        self.row_mut(y)[x] = value;
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        for y in 0..self.height {
+            self.row_mut(y).fill(value);
+        }
+    }
+
+    /// Writes `source` into this window, which must be the same size.
+    pub fn copy_from<R: Raster>(&mut self, source: &R) -> Result<(), Box<dyn std::error::Error>> {
+        if source.width() != self.width || source.height() != self.height {
+            return Err(format!(
+                "Cannot copy {}x{} into a {}x{} window",
+                source.width(),
+                source.height(),
+                self.width,
+                self.height
+            )
+            .into());
+        }
+
+        for y in 0..self.height {
+            self.row_mut(y).copy_from_slice(source.row(y));
+        }
+        Ok(())
+    }
+}
+
+impl Raster for FieldViewMut<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[f32] {
+        let start = (self.y + y) * self.stride + self.x;
+        &self.data[start..start + self.width]
+    }
+}
+
+impl Field {
+    /// The whole field as a view.
+    pub fn view(&self) -> FieldView<'_> {
+        FieldView {
+            data: &self.flattened_field,
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height(),
+            stride: self.width,
+        }
+    }
+
+    pub fn window(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<FieldView<'_>, Box<dyn std::error::Error>> {
+        self.view().window(x, y, width, height)
+    }
+
+    pub fn window_mut(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<FieldViewMut<'_>, Box<dyn std::error::Error>> {
+        check_window(self.width, self.height(), x, y, width, height)?;
+
+        Ok(FieldViewMut {
+            data: &mut self.flattened_field,
+            x,
+            y,
+            width,
+            height,
+            stride: self.width,
+        })
+    }
+
+    /// Writes `source` into the region of this field whose top-left cell is `(x, y)`.
+    pub fn paste<R: Raster>(
+        &mut self,
+        x: usize,
+        y: usize,
+        source: &R,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.window_mut(x, y, source.width(), source.height())?
+            .copy_from(source)
+    }
+}
@@ -0,0 +1,358 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+use crate::field::field::Field;
+use crate::field::view::Raster;
+
+const EARTH_RADIUS: f32 = 6_371_000.0;
+const REFRACTION_COEFFICIENT: f32 = 0.13;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Observer {
+    pub x: usize,
+    pub y: usize,
+    /// eye height above the ground
+    pub observer_height: f32,
+    /// height above the ground of whatever is being looked at
+    pub target_height: f32,
+    /// in cells, `f32::INFINITY` for no limit
+    pub max_radius: f32,
+}
+
+impl Observer {
+    pub fn new(x: usize, y: usize, observer_height: f32, target_height: f32) -> Self {
+        Self {
+            x,
+            y,
+            observer_height,
+            target_height,
+            max_radius: f32::INFINITY,
+        }
+    }
+
+    pub fn with_max_radius(mut self, max_radius: f32) -> Self {
+        self.max_radius = max_radius;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineOfSight {
+    pub visible: bool,
+    /// first cell along the sightline that rises above it
+    pub blocked_at: Option<(usize, usize)>,
+}
+
+/// One step along a sightline.
+struct RayStep {
+    index: usize,
+    x: usize,
+    y: usize,
+    /// horizontal distance from the observer in world units
+    distance: f32,
+    /// interpolated ground height, curvature corrected
+    ground: f32,
+}
+
+impl Field {
+    fn curvature_drop(distance: f32, earth_curvature: bool) -> f32 {
+        if earth_curvature {
+            distance * distance * (1.0 - REFRACTION_COEFFICIENT) / (2.0 * EARTH_RADIUS)
+        } else {
+            0.0
+        }
+    }
+
+    fn check_observer(&self, observer: &Observer) -> Result<(), Box<dyn std::error::Error>> {
+        if observer.x >= self.width || observer.y >= self.height() {
+            return Err(format!(
+                "Observer ({}, {}) is outside the {}x{} field",
+                observer.x,
+                observer.y,
+                self.width,
+                self.height()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Walks from the observer towards `(tx, ty)` one cell along the major axis at a time,
+    /// interpolating the ground height across the minor axis (R2 style).
+    fn trace_ray<F>(
+        &self,
+        observer: &Observer,
+        tx: isize,
+        ty: isize,
+        cell_size: f32,
+        earth_curvature: bool,
+        mut visit: F,
+    ) where
+        F: FnMut(RayStep) -> bool,
+    {
+        let ox = observer.x as f32;
+        let oy = observer.y as f32;
+        let dx = tx as f32 - ox;
+        let dy = ty as f32 - oy;
+        let steps = dx.abs().max(dy.abs()) as usize;
+        let x_major = dx.abs() >= dy.abs();
+
+        for s in 1..=steps {
+            let t = s as f32 / steps as f32;
+            let fx = ox + dx * t;
+            let fy = oy + dy * t;
+
+            let cells = ((fx - ox).powi(2) + (fy - oy).powi(2)).sqrt();
+            if cells > observer.max_radius {
+                break;
+            }
+
+            let x = fx.round() as isize;
+            let y = fy.round() as isize;
+            if x < 0 || y < 0 || x >= self.width as isize || y >= self.height() as isize {
+                break;
+            }
+
+            // interpolate between the two cells straddling the ray on the minor axis
+            let ground = if x_major {
+                let y0 = fy.floor();
+                let w = fy - y0;
+                self.sample(x, y0 as isize) * (1.0 - w) + self.sample(x, y0 as isize + 1) * w
+            } else {
+                let x0 = fx.floor();
+                let w = fx - x0;
+                self.sample(x0 as isize, y) * (1.0 - w) + self.sample(x0 as isize + 1, y) * w
+            };
+
+            let distance = cells * cell_size;
+            let step = RayStep {
+                index: y as usize * self.width + x as usize,
+                x: x as usize,
+                y: y as usize,
+                distance,
+                ground: ground - Self::curvature_drop(distance, earth_curvature),
+            };
+
+            if !visit(step) {
+                break;
+            }
+        }
+    }
+
+    /// Marks the cells visible from `observer` in `visible`, which must start out all false.
+    fn viewshed_mask(
+        &self,
+        observer: &Observer,
+        cell_size: f32,
+        earth_curvature: bool,
+        visible: &[AtomicBool],
+    ) {
+        let width = self.width as isize;
+        let height = self.height() as isize;
+        let eye = self.get(observer.x, observer.y) + observer.observer_height;
+
+        visible[observer.y * self.width + observer.x].store(true, Ordering::Relaxed);
+
+        // rays only need to reach the edge of the search window
+        let reach = observer.max_radius.min((width + height) as f32).ceil() as isize;
+        let left = (observer.x as isize - reach).max(0);
+        let right = (observer.x as isize + reach).min(width - 1);
+        let top = (observer.y as isize - reach).max(0);
+        let bottom = (observer.y as isize + reach).min(height - 1);
+
+        let mut perimeter = Vec::new();
+        for x in left..=right {
+            perimeter.push((x, top));
+            perimeter.push((x, bottom));
+        }
+        for y in top + 1..bottom {
+            perimeter.push((left, y));
+            perimeter.push((right, y));
+        }
+
+        perimeter.par_iter().for_each(|&(tx, ty)| {
+            let mut max_gradient = f32::NEG_INFINITY;
+            self.trace_ray(observer, tx, ty, cell_size, earth_curvature, |step| {
+                let target_gradient = (step.ground + observer.target_height - eye) / step.distance;
+                if target_gradient >= max_gradient {
+                    visible[step.index].store(true, Ordering::Relaxed);
+                }
+                max_gradient = max_gradient.max((step.ground - eye) / step.distance);
+                true
+            });
+        });
+    }
+
+    fn empty_mask(&self) -> Vec<AtomicBool> {
+        (0..self.flattened_field.len())
+            .map(|_| AtomicBool::new(false))
+            .collect()
+    }
+
+    /// Cells visible from `observer` are 1.0, everything else 0.0.
+    ///
+    /// Uses R2 ray casting to every cell on the perimeter of the search window.
+    /// With `earth_curvature` the heights are dropped by d^2 (1 - k) / 2R with the
+    /// standard refraction coefficient, so heights and `cell_size` must be in metres.
+    pub fn viewshed(
+        &self,
+        observer: &Observer,
+        cell_size: f32,
+        earth_curvature: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        self.check_observer(observer)?;
+
+        let visible = self.empty_mask();
+        self.viewshed_mask(observer, cell_size, earth_curvature, &visible);
+        let result: Vec<f32> = visible
+            .into_iter()
+            .map(|v| if v.into_inner() { 1.0 } else { 0.0 })
+            .collect();
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Number of observers that can see each cell.
+    ///
+    /// Observers are traced one after another, each with all threads, so memory stays at
+    /// two field-sized buffers however many observers there are.
+    pub fn cumulative_viewshed(
+        &self,
+        observers: &[Observer],
+        cell_size: f32,
+        earth_curvature: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        for observer in observers {
+            self.check_observer(observer)?;
+        }
+
+        let visible = self.empty_mask();
+        let mut result = vec![0.0; self.flattened_field.len()];
+        for observer in observers {
+            self.viewshed_mask(observer, cell_size, earth_curvature, &visible);
+            result
+                .par_iter_mut()
+                .zip(visible.par_iter())
+                .for_each(|(count, v)| {
+                    if v.swap(false, Ordering::Relaxed) {
+                        *count += 1.0;
+                    }
+                });
+        }
+
+        Ok(Self {
+            flattened_field: result.into_boxed_slice(),
+            width: self.width,
+        })
+    }
+
+    /// Point-to-point sightline from `observer` to `target`, reporting the first blocking cell.
+    pub fn line_of_sight(
+        &self,
+        observer: &Observer,
+        target: (usize, usize),
+        cell_size: f32,
+        earth_curvature: bool,
+    ) -> Result<LineOfSight, Box<dyn std::error::Error>> {
+        Self::check_cell_size(cell_size)?;
+        self.check_observer(observer)?;
+        if target.0 >= self.width || target.1 >= self.height() {
+            return Err(format!("Target {:?} is outside the field", target).into());
+        }
+
+        let eye = self.get(observer.x, observer.y) + observer.observer_height;
+        let cells = ((target.0 as f32 - observer.x as f32).powi(2)
+            + (target.1 as f32 - observer.y as f32).powi(2))
+        .sqrt();
+        if cells > observer.max_radius {
+            return Ok(LineOfSight {
+                visible: false,
+                blocked_at: None,
+            });
+        }
+        if cells == 0.0 {
+            return Ok(LineOfSight {
+                visible: true,
+                blocked_at: None,
+            });
+        }
+
+        let distance = cells * cell_size;
+        let target_top = self.get(target.0, target.1) + observer.target_height
+            - Self::curvature_drop(distance, earth_curvature);
+        let sight_gradient = (target_top - eye) / distance;
+
+        let mut blocked_at = None;
+        self.trace_ray(
+            observer,
+            target.0 as isize,
+            target.1 as isize,
+            cell_size,
+            earth_curvature,
+            |step| {
+                if (step.x, step.y) == target {
+                    return false;
+                }
+                if (step.ground - eye) / step.distance > sight_gradient {
+                    blocked_at = Some((step.x, step.y));
+                    return false;
+                }
+                true
+            },
+        );
+
+        Ok(LineOfSight {
+            visible: blocked_at.is_none(),
+            blocked_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_plane_is_fully_visible() {
+        let plane = Field::from_fn(15, 11, |_, _| 3.0).unwrap();
+        let observer = Observer::new(4, 7, 1.7, 0.0);
+
+        let viewshed = plane.viewshed(&observer, 10.0, false).unwrap();
+        assert!(viewshed.flattened_field.iter().all(|&v| v == 1.0));
+
+        let observers = [
+            observer,
+            Observer::new(0, 0, 1.7, 0.0),
+            Observer::new(14, 10, 0.5, 0.0),
+        ];
+        let cumulative = plane.cumulative_viewshed(&observers, 10.0, false).unwrap();
+        assert!(cumulative.flattened_field.iter().all(|&v| v == 3.0));
+    }
+
+    #[test]
+    fn wall_blocks_the_far_side() {
+        let walled = Field::from_fn(15, 5, |x, _| if x == 7 { 50.0 } else { 0.0 }).unwrap();
+        let observer = Observer::new(2, 2, 1.7, 0.0);
+
+        let viewshed = walled.viewshed(&observer, 1.0, false).unwrap();
+        assert!((0..5).all(|y| viewshed.get(5, y) == 1.0 && viewshed.get(12, y) == 0.0));
+
+        let sight = walled
+            .line_of_sight(&observer, (12, 2), 1.0, false)
+            .unwrap();
+        assert_eq!(
+            sight,
+            LineOfSight {
+                visible: false,
+                blocked_at: Some((7, 2)),
+            }
+        );
+        assert!(walled.viewshed(&observer, 0.0, false).is_err());
+    }
+}
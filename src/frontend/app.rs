@@ -2,11 +2,13 @@ use eframe::egui::{self, TextureOptions};
 
 use crate::field::field::Field;
 
+#[allow(dead_code)]
 pub(crate) struct App {
     texture: Option<egui::TextureHandle>,
     image_dimensions: [usize; 2],
 }
 
+#[allow(dead_code)]
 impl App {
     pub fn new(image_dimensions: [usize; 2]) -> Self {
         Self {
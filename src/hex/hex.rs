@@ -140,6 +140,7 @@ impl FractionalHex {
 
 impl Eq for FractionalHex {}
 
+#[allow(clippy::derive_ord_xor_partial_ord)]
 impl Ord for FractionalHex {
     fn cmp(&self, other: &Self) -> Ordering {
         self.round().cmp(&other.round())
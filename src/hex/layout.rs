@@ -28,7 +28,6 @@ impl Layout {
         }
     }
 
-    #[allow(dead_code)]
     fn hex_corner_offset(&self, i: u8) -> Point {
         let angle = 2.0 * std::f64::consts::PI * (self.orientation.start_angle - i as f64) / 6.0;
         Point {
@@ -37,10 +36,9 @@ impl Layout {
         }
     }
 
-    #[allow(dead_code)]
     pub fn polygon_corners(&self, hex: &Hex) -> Vec<Point> {
         let mut corners: Vec<Point> = Vec::new();
-        let center = Point::from_hex(&self, &hex);
+        let center = Point::from_hex(self, hex);
 
         for i in 0..6 {
             let offset = self.hex_corner_offset(i);
@@ -1,4 +1,5 @@
 pub mod point;
+#[allow(clippy::module_inception)]
 pub mod hex;
 pub mod layout;
 pub mod orientation;
\ No newline at end of file
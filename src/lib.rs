@@ -0,0 +1,3 @@
+pub mod field;
+pub mod hex;
+pub mod frontend;
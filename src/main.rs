@@ -1,10 +1,7 @@
-pub mod field;
-pub mod hex;
-pub mod frontend;
-
-use field::field::Field;
-use hex::{layout::Layout, point::Point};
-//use frontend::app::App;
+use probable_eureka::field::field::Field;
+use probable_eureka::field::view::Raster;
+use probable_eureka::hex::{layout::Layout, point::Point};
+//use probable_eureka::frontend::app::App;
 
 extern crate image;
 
@@ -37,7 +34,7 @@ fn raw_image_to_normal(
 }
 */
 
-fn stem_builder<'a>(p: &Path, postfix: &str) -> String {
+fn stem_builder(p: &Path, postfix: &str) -> String {
     let stem = p.file_stem().expect("file has no stem").to_string_lossy();
     let result = format!("{}_{}", stem, postfix);
     result